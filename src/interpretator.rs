@@ -1,12 +1,17 @@
-use super::{nodes::*, parser::*};
+use super::{nodes::*, parser::*, prelude};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::error;
 use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum InterpretatorError {
     CastError(String),
     EvaluationError(String),
+    JsonError(String),
 }
 impl error::Error for InterpretatorError {}
 
@@ -15,6 +20,7 @@ impl fmt::Display for InterpretatorError {
         match self {
             InterpretatorError::CastError(s) => write!(f, "CastError: {}", s),
             InterpretatorError::EvaluationError(s) => write!(f, "EvaluationError: {}", s),
+            InterpretatorError::JsonError(s) => write!(f, "JsonError: {}", s),
         }
     }
 }
@@ -28,6 +34,7 @@ pub trait Cast {
 impl Cast for Value {
     fn cast_to_number(&self) -> Result<f64, InterpretatorError> {
         match self {
+            Value::Integer(i) => Ok(*i as f64),
             Value::Number(n) => Ok(*n),
             Value::Boolean(b) => Ok((*b as i64) as f64),
             Value::String(s) => match s.parse::<f64>() {
@@ -37,23 +44,16 @@ impl Cast for Value {
                     s
                 ))),
             },
-            Value::List(_) => Err(InterpretatorError::CastError(
-                "Cannot cast list to number".to_string(),
-            )),
-            Value::Map(_) => Err(InterpretatorError::CastError(
-                "Cannot cast map to number".to_string(),
-            )),
-            Value::Function(_) => Err(InterpretatorError::CastError(
-                "Cannot cast function to number".to_string(),
-            )),
-            Value::Null => Err(InterpretatorError::CastError(
-                "Cannot cast null to number".to_string(),
-            )),
+            v => Err(InterpretatorError::CastError(format!(
+                "Cannot cast {} to number",
+                v.type_name()
+            ))),
         }
     }
 
     fn cast_to_bool(&self) -> Result<bool, InterpretatorError> {
         match self {
+            Value::Integer(i) => Ok(*i != 0),
             Value::Number(n) => Ok(*n != 0.0),
             Value::Boolean(b) => Ok(*b),
             Value::String(s) => match s.parse::<bool>() {
@@ -63,152 +63,835 @@ impl Cast for Value {
                     s
                 ))),
             },
-            Value::List(_) => Err(InterpretatorError::CastError(
-                "Cannot cast list to bool".to_string(),
-            )),
-            Value::Map(_) => Err(InterpretatorError::CastError(
-                "Cannot cast map to bool".to_string(),
-            )),
-            Value::Function(_) => Err(InterpretatorError::CastError(
-                "Cannot cast function to bool".to_string(),
-            )),
-            Value::Null => Err(InterpretatorError::CastError(
-                "Cannot cast null to bool".to_string(),
-            )),
+            v => Err(InterpretatorError::CastError(format!(
+                "Cannot cast {} to bool",
+                v.type_name()
+            ))),
         }
     }
 
+    /// Keywords, lists, pairs, maps and null are written the way they print, so
+    /// `(str [1 2])` gives "[1 2]".
     fn cast_to_string(&self) -> Result<String, InterpretatorError> {
         match self {
-            Value::Number(n) => Ok(n.to_string()),
+            Value::Integer(_) | Value::Number(_) => Ok(self.to_string()),
             Value::Boolean(b) => Ok(b.to_string()),
             Value::String(s) => Ok(s.clone()),
-            Value::List(_) => Err(InterpretatorError::CastError(
-                "Cannot cast list to string".to_string(),
-            )),
-            Value::Map(_) => Err(InterpretatorError::CastError(
-                "Cannot cast map to string".to_string(),
-            )),
-            Value::Function(_) => Err(InterpretatorError::CastError(
-                "Cannot cast function to string".to_string(),
-            )),
-            Value::Null => Err(InterpretatorError::CastError(
-                "Cannot cast null to string".to_string(),
-            )),
+            Value::Char(c) => Ok(c.to_string()),
+            Value::Symbol(s) => Ok(s.clone()),
+            Value::Keyword(_)
+            | Value::List(_)
+            | Value::Pair(_, _)
+            | Value::Map(_)
+            | Value::Null => Ok(self.to_string()),
+            Value::Function(_) | Value::Quoted(_) => Err(InterpretatorError::CastError(format!(
+                "Cannot cast {} to string",
+                self.type_name()
+            ))),
         }
     }
 }
-#[derive(Debug)]
-pub struct Scope<'a> {
+/// A scope of variable bindings. Scopes are shared handles: cloning one
+/// refers to the same bindings, which lets functions keep the scope they
+/// were defined in alive for as long as they exist.
+#[derive(Clone)]
+pub struct Scope(Rc<RefCell<ScopeData>>);
+
+pub struct ScopeData {
     pub variables: HashMap<String, Value>,
-    pub parent: Option<Box<&'a Scope<'a>>>,
+    pub parent: Option<Scope>,
 }
 
-impl<'a> Scope<'a> {
-    pub fn new(parent: Option<&'a Scope>) -> Scope<'a> {
-        let parent_scope = match parent {
-            Some(s) => Some(Box::new(s)),
-            None => None,
-        };
-        Scope {
+impl Scope {
+    pub fn new(parent: Option<&Scope>) -> Scope {
+        Scope(Rc::new(RefCell::new(ScopeData {
             variables: HashMap::new(),
-            parent: parent_scope,
+            parent: parent.cloned(),
+        })))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let data = self.0.borrow();
+        match data.variables.get(name) {
+            Some(v) => Some(v.clone()),
+            None => data.parent.as_ref().and_then(|p| p.get(name)),
         }
     }
 
-    pub fn get(&self, name: &str) -> Option<&Value> {
-        match self.variables.get(name) {
-            Some(v) => Some(v),
-            None => match &self.parent {
-                Some(p) => p.get(name),
-                None => None,
-            },
+    pub fn set(&self, name: String, value: Value) {
+        self.0.borrow_mut().variables.insert(name, value);
+    }
+
+    /// Rebinds `name` in the nearest scope that defines it. Returns false if
+    /// the name is not defined anywhere in the chain.
+    pub fn update(&self, name: &str, value: Value) -> bool {
+        let mut data = self.0.borrow_mut();
+        if let Some(v) = data.variables.get_mut(name) {
+            *v = value;
+            return true;
+        }
+        match &data.parent {
+            Some(p) => p.update(name, value),
+            None => false,
+        }
+    }
+}
+
+/// Scopes are compared by identity, since two scopes with the same
+/// bindings can still diverge later.
+impl PartialEq for Scope {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Only the names bound directly in the scope are shown: a scope can hold
+/// functions that refer back to it.
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<String> = self.0.borrow().variables.keys().cloned().collect();
+        names.sort();
+        f.debug_struct("Scope").field("names", &names).finish()
+    }
+}
+
+/// Default limit on nested function calls, low enough that hitting it
+/// returns an error before the native stack overflows.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// A source of the current time, which tests can replace with a fake.
+pub trait Clock {
+    /// Seconds since the Unix epoch.
+    fn now(&self) -> f64;
+}
+
+/// The system's wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0)
+    }
+}
+
+/// A source of environment variables, which tests can replace with a fake.
+pub trait Environment {
+    /// The value of the variable `name`, if it is set.
+    fn var(&self, name: &str) -> Option<String>;
+}
+
+/// The environment of the running process.
+pub struct SystemEnvironment;
+
+impl Environment for SystemEnvironment {
+    fn var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+impl Environment for HashMap<String, String> {
+    fn var(&self, name: &str) -> Option<String> {
+        self.get(name).cloned()
+    }
+}
+
+/// A callback receiving each node before it is evaluated, and the call
+/// depth it is evaluated at.
+pub type TraceHook = Box<dyn FnMut(&Node, usize)>;
+
+/// Interpretator state threaded through every evaluation step.
+pub struct Context {
+    pub max_depth: usize,
+    pub depth: usize,
+    /// Where `print` and `println` write to.
+    pub output: Box<dyn Write>,
+    /// Where `read-line` and `read-number` read from.
+    pub input: Box<dyn BufRead>,
+    /// Number of names handed out by `gensym` so far.
+    pub gensym_counter: usize,
+    /// State of the xorshift generator behind `random`, never zero.
+    pub rng_state: u64,
+    /// The largest list or map builtins may construct, if limited.
+    pub max_collection_size: Option<usize>,
+    /// The most times a single `while` loop may run its body, if limited.
+    pub max_iterations: Option<usize>,
+    /// Called with every node about to be evaluated and the current depth.
+    pub trace: Option<TraceHook>,
+    /// What `now` and `time` read the time from.
+    pub clock: Box<dyn Clock>,
+    /// Where `env` looks variables up.
+    pub env: Box<dyn Environment>,
+    /// The command line arguments `args` returns.
+    pub args: Vec<String>,
+    /// Whether `slurp` and `spit` may touch the filesystem. Off by default.
+    pub filesystem_access: bool,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context {
+            max_depth: DEFAULT_MAX_DEPTH,
+            depth: 0,
+            output: Box::new(io::stdout()),
+            // A one byte buffer never holds on to input past the line being
+            // read, so stdin can still be read elsewhere, as the REPL does.
+            input: Box::new(io::BufReader::with_capacity(1, io::stdin())),
+            gensym_counter: 0,
+            rng_state: seed_rng(SystemClock.now().to_bits()),
+            max_collection_size: None,
+            max_iterations: None,
+            trace: None,
+            clock: Box::new(SystemClock),
+            env: Box::new(SystemEnvironment),
+            args: vec![],
+            filesystem_access: false,
         }
     }
 
-    pub fn set(&mut self, name: String, value: Value) {
-        self.variables.insert(name, value);
+    /// Fails when a collection of `size` elements would exceed
+    /// `max_collection_size`.
+    pub fn check_collection_size(&self, size: usize) -> Result<(), String> {
+        match self.max_collection_size {
+            Some(max) if size > max => Err("collection size limit exceeded".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails when a loop that has already run `iterations` times would
+    /// exceed `max_iterations` by running again.
+    pub fn check_iterations(&self, iterations: usize) -> Result<(), String> {
+        match self.max_iterations {
+            Some(max) if iterations >= max => Err("iteration limit exceeded".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Scrambles `seed` into a generator state, so that similar seeds give
+/// unrelated sequences and no seed gives the all-zero state.
+pub fn seed_rng(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    match z ^ (z >> 31) {
+        0 => 1,
+        z => z,
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::new()
     }
 }
 
-pub struct Interpretator<'a> {
-    pub global_scope: Scope<'a>,
+pub struct Interpretator {
+    pub global_scope: Scope,
+    pub context: Context,
 }
 
-impl<'a> Interpretator<'a> {
-    pub fn new(global_scope: Option<Scope<'a>>) -> Interpretator<'a> {
+impl Interpretator {
+    pub fn new(global_scope: Option<Scope>) -> Interpretator {
         let global_scope = match global_scope {
             Some(s) => s,
             None => Scope::new(None),
         };
+        prelude::register(&global_scope);
+        let mut interpretator = Interpretator {
+            global_scope,
+            context: Context::new(),
+        };
+        interpretator
+            .run(prelude::SOURCE.to_owned())
+            .expect("the prelude evaluates without errors");
+        interpretator
+    }
+
+    /// An interpretator without any builtins, so that embedders can bind
+    /// only the natives they want to expose. Neither the natives nor the
+    /// Lisp prelude are loaded. Special forms such as `def` and `if` are
+    /// still available.
+    pub fn bare() -> Interpretator {
         Interpretator {
-            global_scope: global_scope,
+            global_scope: Scope::new(None),
+            context: Context::new(),
         }
     }
 
+    pub fn with_max_depth(mut self, max_depth: usize) -> Interpretator {
+        self.context.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_max_collection_size(mut self, max_collection_size: usize) -> Interpretator {
+        self.context.max_collection_size = Some(max_collection_size);
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Interpretator {
+        self.context.max_iterations = Some(max_iterations);
+        self
+    }
+
+    pub fn with_trace(mut self, trace: TraceHook) -> Interpretator {
+        self.context.trace = Some(trace);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Interpretator {
+        self.context.clock = clock;
+        self
+    }
+
+    pub fn with_env(mut self, env: Box<dyn Environment>) -> Interpretator {
+        self.context.env = env;
+        self
+    }
+
+    pub fn with_args(mut self, args: Vec<String>) -> Interpretator {
+        self.context.args = args;
+        self
+    }
+
+    pub fn with_filesystem_access(mut self, allowed: bool) -> Interpretator {
+        self.context.filesystem_access = allowed;
+        self
+    }
+
+    pub fn with_output(mut self, output: Box<dyn Write>) -> Interpretator {
+        self.context.output = output;
+        self
+    }
+
+    pub fn with_input(mut self, input: Box<dyn BufRead>) -> Interpretator {
+        self.context.input = input;
+        self
+    }
+
     pub fn run(&mut self, source: String) -> Result<Value, Box<dyn error::Error>> {
         let mut parser = Parser::from_source(source)?;
-        let program = parser.parseProgram()?;
+        let program = parser.parse_program()?;
+        Ok(self.eval_node(&program)?)
+    }
+
+    /// Like `run`, but returns the value of every top-level form in order.
+    /// Evaluation stops at the first error.
+    pub fn run_all(&mut self, source: String) -> Result<Vec<Value>, Box<dyn error::Error>> {
+        let mut values = vec![];
+        for form in Parser::from_source(source)? {
+            values.push(self.eval_node(&form?)?);
+        }
+        Ok(values)
+    }
 
-        match program.evaluate(&self.global_scope) {
-            Ok(v) => Ok(v),
-            Err(e) => Err(Box::new(InterpretatorError::EvaluationError(e))),
+    /// Lexes and parses `source` without evaluating it, and returns every
+    /// syntax error found. Parsing resumes after a broken form, but a lexer
+    /// error ends the check, since the tokens after it are unknown.
+    pub fn check(&self, source: &str) -> Result<(), Vec<Box<dyn error::Error>>> {
+        let errors: Vec<Box<dyn error::Error>> = match Parser::from_source(source.to_owned()) {
+            Ok(mut parser) => parser
+                .check()
+                .into_iter()
+                .map(|e| Box::new(e) as Box<dyn error::Error>)
+                .collect(),
+            Err(e) => vec![Box::new(e)],
+        };
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
+
+    /// Evaluates an already parsed syntax tree in the global scope.
+    pub fn eval_node(&mut self, node: &Node) -> Result<Value, InterpretatorError> {
+        self.context.depth = 0;
+        node.evaluate(&self.global_scope, &mut self.context)
+            .map_err(InterpretatorError::EvaluationError)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_cast_to_string() {
+        assert_eq!(Value::Number(1.5).cast_to_string().unwrap(), "1.5");
+        assert_eq!(Value::Boolean(true).cast_to_string().unwrap(), "true");
+        assert_eq!(Value::Char('a').cast_to_string().unwrap(), "a");
+        assert!(Value::Quoted(Box::new(Node::Variable("x".to_string())))
+            .cast_to_string()
+            .is_err());
+    }
+
+    #[test]
+    fn test_cast_collections_to_string() {
+        let list = Value::List(vec![
+            Node::Atom(Value::Integer(1)),
+            Node::Atom(Value::String("a".to_string())),
+        ]);
+        assert_eq!(list.cast_to_string().unwrap(), "[1 \"a\"]");
+        let mut map = HashMap::new();
+        map.insert(MapKey::String("k".to_string()), Value::Integer(2));
+        assert_eq!(Value::Map(map).cast_to_string().unwrap(), "{\"k\" 2}");
+        assert_eq!(Value::Null.cast_to_string().unwrap(), "nil");
+    }
+
+    #[test]
+    fn test_str_of_collections() {
+        let mut interpretator = Interpretator::new(None);
+        let result = interpretator.run("(str [1 2 3] \" \" nil)".to_string());
+        assert_eq!(result.unwrap(), Value::String("[1 2 3] nil".to_string()));
+    }
+
     #[test]
     fn test_interpretator_scope() {
-        let mut scope = Scope::new(None);
+        let scope = Scope::new(None);
         scope.set("a".to_string(), Value::Number(1.0));
-        assert_eq!(scope.get("a"), Some(&Value::Number(1.0)));
+        assert_eq!(scope.get("a"), Some(Value::Number(1.0)));
         assert_eq!(scope.get("b"), None);
     }
 
     #[test]
     fn test_interpretator_scope_parent() {
-        let mut parent = Scope::new(None);
+        let parent = Scope::new(None);
         parent.set("a".to_string(), Value::Number(1.0));
         let scope = Scope::new(Some(&parent));
-        assert_eq!(scope.get("a"), Some(&Value::Number(1.0)));
+        assert_eq!(scope.get("a"), Some(Value::Number(1.0)));
         assert_eq!(scope.get("b"), None);
     }
 
     #[test]
     fn test_interpretator_scope_parent_parent() {
-        let mut parent = Scope::new(None);
+        let parent = Scope::new(None);
         parent.set("a".to_string(), Value::Number(1.0));
-        let mut parent2 = Scope::new(Some(&parent));
+        let parent2 = Scope::new(Some(&parent));
         parent2.set("b".to_string(), Value::Number(2.0));
         let scope = Scope::new(Some(&parent2));
-        assert_eq!(scope.get("a"), Some(&Value::Number(1.0)));
-        assert_eq!(scope.get("b"), Some(&Value::Number(2.0)));
+        assert_eq!(scope.get("a"), Some(Value::Number(1.0)));
+        assert_eq!(scope.get("b"), Some(Value::Number(2.0)));
         assert_eq!(scope.get("c"), None);
     }
 
     #[test]
     fn test_interpretator_redefining_parent_scope_variable() {
-        let mut parent = Scope::new(None);
+        let parent = Scope::new(None);
         parent.set("a".to_string(), Value::Number(1.0));
-        let mut scope = Scope::new(Some(&parent));
+        let scope = Scope::new(Some(&parent));
         scope.set("a".to_string(), Value::Number(2.0));
-        assert_eq!(scope.get("a"), Some(&Value::Number(2.0)));
-        assert_eq!(parent.get("a"), Some(&Value::Number(1.0)));
+        assert_eq!(scope.get("a"), Some(Value::Number(2.0)));
+        assert_eq!(parent.get("a"), Some(Value::Number(1.0)));
     }
 
     #[test]
-    fn test_interpretator_initialize_without_global_scope() {
+    fn test_interpretator_update_parent_scope_variable() {
+        let parent = Scope::new(None);
+        parent.set("a".to_string(), Value::Number(1.0));
+        let scope = Scope::new(Some(&parent));
+        assert!(scope.update("a", Value::Number(2.0)));
+        assert_eq!(parent.get("a"), Some(Value::Number(2.0)));
+        assert!(!scope.update("b", Value::Number(3.0)));
+        assert_eq!(scope.get("b"), None);
+    }
+
+    #[test]
+    fn test_interpretator_when_unless() {
+        let mut interpretator = Interpretator::new(None);
+        let mut run = |source: &str| {
+            interpretator
+                .run(source.to_owned())
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            run("(def a 0) (when (= a 0) (set! a 1) (+ a 1))"),
+            Ok(Value::Integer(2))
+        );
+        assert_eq!(run("(when false 1)"), Ok(Value::Null));
+        assert_eq!(run("(when true)"), Ok(Value::Null));
+        assert!(run("(when)").is_err());
+        assert_eq!(
+            run("(def b 0) (when false (set! b 1) (undefined)) b"),
+            Ok(Value::Integer(0))
+        );
+        assert_eq!(run("(when nil (undefined))"), Ok(Value::Null));
+        assert_eq!(run("(unless false 1 2)"), Ok(Value::Integer(2)));
+        assert_eq!(
+            run("(def c 0) (unless true (set! c 1) (undefined)) c"),
+            Ok(Value::Integer(0))
+        );
+        assert_eq!(
+            run("(unless nil \"ran\")"),
+            Ok(Value::String("ran".to_string()))
+        );
+        assert!(matches!(
+            run("when"),
+            Ok(Value::Function(Function::Macro(_)))
+        ));
+    }
+
+    #[test]
+    fn test_interpretator_closure_keeps_scope() {
+        let mut interpretator = Interpretator::new(None);
+        interpretator
+            .run("(defn make-adder [n] (fn [x] (+ x n))) (def add2 (make-adder 2))".to_string())
+            .unwrap();
+        let scope = match interpretator.global_scope.get("add2") {
+            Some(Value::Function(Function::UserDefined(f))) => f.scope,
+            other => panic!("expected a function, got {:?}", other),
+        };
+        assert_eq!(scope.get("n"), Some(Value::Integer(2)));
+        assert_eq!(interpretator.global_scope.get("n"), None);
+        assert_eq!(
+            interpretator.run("(add2 3)".to_string()).unwrap(),
+            Value::Integer(5)
+        );
+        scope.set("n".to_string(), Value::Integer(10));
+        assert_eq!(
+            interpretator.run("(add2 3)".to_string()).unwrap(),
+            Value::Integer(13)
+        );
+    }
+
+    #[test]
+    fn test_interpretator_bare() {
+        let mut bare = Interpretator::bare();
+        assert_eq!(
+            bare.run("(+ 1 2)".to_string()).map_err(|e| e.to_string()),
+            Err("EvaluationError: + is not defined".to_string())
+        );
+        assert_eq!(
+            bare.run("(def a 1) (if a [a] nil)".to_string()).unwrap(),
+            Value::List(vec![Node::Atom(Value::Integer(1))])
+        );
         let mut interpretator = Interpretator::new(None);
+        assert_eq!(
+            interpretator.run("(+ 1 2)".to_string()).unwrap(),
+            Value::Integer(3)
+        );
+    }
+
+    #[test]
+    fn test_interpretator_lisp_prelude() {
+        let mut interpretator = Interpretator::new(None);
+        let mut run = |source: &str| {
+            interpretator
+                .run(source.to_owned())
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(run("(map inc [1 2 3])"), run("[2 3 4]"));
+        assert_eq!(run("(filter (fn [n] (> n 1)) [1 2 3])"), run("[2 3]"));
+        assert_eq!(run("(remove (fn [n] (> n 1)) [1 2 3])"), run("[1]"));
+        assert_eq!(run("(map inc [])"), run("[]"));
+        assert_eq!(
+            run("[(not false) (not nil) (not 1)]"),
+            run("[true true false]")
+        );
+        assert_eq!(
+            run("(def not-map? (complement map?)) [(not-map? 1) (not-map? {})]"),
+            run("[true false]")
+        );
+        assert!(matches!(
+            run("remove"),
+            Ok(Value::Function(Function::UserDefined(_)))
+        ));
+        assert_eq!(
+            Interpretator::bare()
+                .run("(when true 1)".to_owned())
+                .map_err(|e| e.to_string()),
+            Err("EvaluationError: when is not defined".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpretator_initialize_without_global_scope() {
+        let interpretator = Interpretator::new(None);
         interpretator
             .global_scope
             .set("a".to_string(), Value::Number(1.0));
         assert_eq!(
             interpretator.global_scope.get("a").unwrap(),
-            &Value::Number(1.0)
+            Value::Number(1.0)
+        );
+    }
+
+    #[test]
+    fn test_interpretator_recursion_limit() {
+        let mut interpretator = Interpretator::new(None).with_max_depth(50);
+        interpretator
+            .run("(defn forever [x] (+ 1 (forever x)))".to_owned())
+            .unwrap();
+        let err = interpretator.run("(forever 1)".to_owned()).unwrap_err();
+        assert_eq!(err.to_string(), "EvaluationError: recursion limit exceeded");
+    }
+
+    #[test]
+    fn test_interpretator_iteration_limit() {
+        let mut interpretator = Interpretator::new(None).with_max_iterations(10);
+        let result = interpretator.run("(def i 0) (while (< i 10) (set! i (+ i 1))) i".to_string());
+        assert!(matches!(result, Ok(Value::Integer(10))));
+        let err = interpretator.run("(while true 1)".to_string()).unwrap_err();
+        assert_eq!(err.to_string(), "EvaluationError: iteration limit exceeded");
+    }
+
+    #[test]
+    fn test_interpretator_default_recursion_limit() {
+        let mut interpretator = Interpretator::new(None);
+        let err = interpretator
+            .run("(defn forever [x] (+ 1 (forever x))) (forever 1)".to_owned())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "EvaluationError: recursion limit exceeded");
+    }
+
+    #[test]
+    fn test_interpretator_tail_calls_do_not_grow_depth() {
+        let mut interpretator = Interpretator::new(None).with_max_depth(10);
+        let result = interpretator.run(
+            "(defn count-to [i n] (if (= i n) i (count-to (+ i 1) n)))
+             (count-to 0 1000000)"
+                .to_owned(),
+        );
+        assert!(matches!(result, Ok(Value::Integer(1000000))));
+    }
+
+    #[test]
+    fn test_interpretator_tail_calls_in_cond_and_do() {
+        let mut interpretator = Interpretator::new(None).with_max_depth(10);
+        let result = interpretator.run(
+            "(defn count-to [i n]
+               (cond [(< i n) (do (def next (+ i 1)) (count-to next n))]
+                     [true i]))
+             (count-to 0 10000)"
+                .to_owned(),
+        );
+        assert!(matches!(result, Ok(Value::Integer(10000))));
+        let result = interpretator.run(
+            "(def total 0)
+             (defn add-up [i] (when (> i 0) (set! total (+ total i)) (add-up (- i 1))))
+             (add-up 10000)
+             total"
+                .to_owned(),
+        );
+        assert!(matches!(result, Ok(Value::Integer(50005000))));
+    }
+
+    #[test]
+    fn test_interpretator_non_tail_recursion_still_limited() {
+        let mut interpretator = Interpretator::new(None).with_max_depth(10);
+        let err = interpretator
+            .run("(defn sum-to [n] (if (= n 0) 0 (+ n (sum-to (+ n -1))))) (sum-to 100)".to_owned())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "EvaluationError: recursion limit exceeded");
+    }
+
+    #[test]
+    fn test_interpretator_collection_size_limit() {
+        let mut interpretator = Interpretator::new(None).with_max_collection_size(10);
+        let result = interpretator.run("(count (range 10))".to_string());
+        assert!(matches!(result, Ok(Value::Integer(10))));
+        let err = interpretator
+            .run("(range 1000000000)".to_string())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "EvaluationError: collection size limit exceeded"
+        );
+        let err = interpretator
+            .run("(append (range 6) (range 6))".to_string())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "EvaluationError: collection size limit exceeded"
+        );
+    }
+
+    #[test]
+    fn test_interpretator_eval_node() {
+        let mut interpretator = Interpretator::new(None);
+        let node = Node::FunctionCall(
+            "+".to_string(),
+            vec![
+                Node::Atom(Value::Number(1.0)),
+                Node::FunctionCall(
+                    "*".to_string(),
+                    vec![
+                        Node::Atom(Value::Number(2.0)),
+                        Node::Atom(Value::Number(3.0)),
+                    ],
+                ),
+            ],
+        );
+        assert_eq!(interpretator.eval_node(&node), Ok(Value::Number(7.0)));
+        let node = Node::Variable("undefined".to_string());
+        assert_eq!(
+            interpretator.eval_node(&node),
+            Err(InterpretatorError::EvaluationError(
+                "undefined is not defined".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_interpretator_splice() {
+        let mut interpretator = Interpretator::new(None);
+        let mut run = |source: &str| {
+            interpretator
+                .run(source.to_owned())
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(run("(def xs [2 3]) (+ 1 @xs 4)"), Ok(Value::Integer(10)));
+        assert_eq!(run("(+ @[] @(range 3) @xs)"), Ok(Value::Integer(8)));
+        assert_eq!(run("(defn pair [a b] [b a]) (pair @xs)"), run("[3 2]"));
+        assert_eq!(run("(pair 1 @[2])"), run("[2 1]"));
+        assert_eq!(
+            run("(pair @[1 2 3])"),
+            Err("EvaluationError: Function pair takes 2 arguments, but 3 were given".to_string())
+        );
+        assert_eq!(run("(list @'[a b])"), run("'[a b]"));
+        assert_eq!(
+            run("(+ 1 @5)"),
+            Err("EvaluationError: cannot splice 5: expected a list, got integer 5".to_string())
+        );
+        assert_eq!(
+            run("[1 @xs]"),
+            Err("EvaluationError: @xs can only be used in the arguments of a call".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpretator_call_errors() {
+        let mut interpretator = Interpretator::new(None);
+        assert_eq!(
+            interpretator
+                .run("(def x 5) (x 1)".to_string())
+                .map_err(|e| e.to_string()),
+            Err("EvaluationError: cannot call x: expected a function, got integer 5".to_string())
+        );
+        assert_eq!(
+            interpretator
+                .run("(def s \"hi\") (s)".to_string())
+                .map_err(|e| e.to_string()),
+            Err(
+                "EvaluationError: cannot call s: expected a function, got string \"hi\""
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            interpretator
+                .run("(missing 1)".to_string())
+                .map_err(|e| e.to_string()),
+            Err("EvaluationError: missing is not defined".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpretator_trace() {
+        let nodes = Rc::new(RefCell::new(vec![]));
+        let seen = Rc::clone(&nodes);
+        let mut interpretator =
+            Interpretator::new(None).with_trace(Box::new(move |node, depth| {
+                seen.borrow_mut().push((node.to_string(), depth))
+            }));
+        let result = interpretator.run("(defn f [x] (* x 2)) (+ 1 (f 3))".to_string());
+        assert!(matches!(result, Ok(Value::Integer(7))));
+        let nodes = nodes.borrow();
+        assert_eq!(nodes.len(), 9);
+        assert_eq!(nodes[2], ("(+ 1 (f 3))".to_string(), 0));
+        assert_eq!(nodes[6], ("(* x 2)".to_string(), 1));
+        assert_eq!(nodes[8], ("2".to_string(), 1));
+    }
+
+    #[test]
+    fn test_interpretator_empty_program() {
+        let mut interpretator = Interpretator::new(None);
+        assert_eq!(interpretator.run("".to_string()).unwrap(), Value::Null);
+        assert_eq!(interpretator.run(" \n\t".to_string()).unwrap(), Value::Null);
+        assert_eq!(
+            interpretator
+                .run("; nothing here\n  ; or here".to_string())
+                .unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            Parser::new(vec![]).parse_program(),
+            Ok(Node::Program(vec![Node::EOF]))
+        );
+    }
+
+    #[test]
+    fn test_interpretator_run_all() {
+        let mut interpretator = Interpretator::new(None);
+        let values = interpretator
+            .run_all("(def a 2)\n(* a 3)\n\"done\"\n".to_string())
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                Value::Integer(2),
+                Value::Integer(6),
+                Value::String("done".to_string())
+            ]
+        );
+        assert_eq!(interpretator.run_all("".to_string()).unwrap(), vec![]);
+        assert!(interpretator
+            .run_all("1 (undefined) 3".to_string())
+            .is_err());
+    }
+
+    fn check(source: &str) -> Result<(), Vec<String>> {
+        Interpretator::new(None)
+            .check(source)
+            .map_err(|errors| errors.iter().map(|e| e.to_string()).collect())
+    }
+
+    #[test]
+    fn test_interpretator_check() {
+        assert_eq!(check("(def a 1)\n(undefined a)\n[1 2 . 3]"), Ok(()));
+        assert_eq!(check(""), Ok(()));
+        assert_eq!(
+            check("(def a 1) (f ]"),
+            Err(vec![
+                "Unexpected token at 1:14: CloseBracket expected ) but found ]".to_string()
+            ])
+        );
+        assert_eq!(
+            check("(def a \"oops)"),
+            Err(vec!["LexerError: Unclosed string : oops)".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_interpretator_check_reports_every_error() {
+        assert_eq!(
+            check("(1 2)\n(def a 1)\n[a }\n. 'x\n)\n(ok"),
+            Err(vec![
+                "Unexpected token at 1:2: Integer(1) Integer(1) is not a variable".to_string(),
+                "Unexpected token at 3:4: CloseBrace expected ] but found }".to_string(),
+                "Unexpected token at 4:1: Dot outside of a list".to_string(),
+                "Unexpected token at 5:1: CloseParen unmatched )".to_string(),
+                "Unexpected end of file".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_interpretator_definitions_persist_between_runs() {
+        let mut interpretator = Interpretator::new(None);
+        interpretator
+            .run("(defn pick-second [a b] b)".to_owned())
+            .unwrap();
+        assert!(matches!(
+            interpretator.run("(pick-second 1 2)".to_owned()).unwrap(),
+            Value::Integer(2)
+        ));
+        assert_eq!(
+            interpretator
+                .run("(pick-second \"a\" \"b\")".to_owned())
+                .unwrap(),
+            Value::String("b".to_string())
         );
     }
 }
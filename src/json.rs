@@ -0,0 +1,474 @@
+use super::interpretator::InterpretatorError;
+use super::nodes::*;
+use std::collections::HashMap;
+
+impl Value {
+    /// Parses a JSON document. Objects become maps, arrays become lists
+    /// and `null` becomes null.
+    pub fn from_json(source: &str) -> Result<Value, InterpretatorError> {
+        let mut parser = JsonParser {
+            chars: source.chars().collect(),
+            pos: 0,
+            depth: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// Serializes the value as JSON. Lists become arrays, pairs two element
+    /// arrays and maps objects with their keys in sorted order. Chars become
+    /// one character strings and keywords their name, both as values and as
+    /// keys. Whole floats keep a `.0` so they read back as floats.
+    /// Functions, quoted forms and numbers JSON cannot represent, such as
+    /// NaN, are written as `null`.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        write_json(self, &mut out);
+        out
+    }
+}
+
+fn write_json(value: &Value, out: &mut String) {
+    match value {
+        Value::Integer(i) => out.push_str(&i.to_string()),
+        Value::Number(n) if n.is_finite() => {
+            let number = n.to_string();
+            out.push_str(&number);
+            if !number.contains('.') {
+                out.push_str(".0");
+            }
+        }
+        Value::String(s) => write_json_string(s, out),
+        Value::Char(c) => write_json_string(&c.to_string(), out),
+        Value::Keyword(k) | Value::Symbol(k) => write_json_string(k, out),
+        Value::Boolean(b) => out.push_str(&b.to_string()),
+        Value::List(nodes) => {
+            out.push('[');
+            for (i, node) in nodes.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                match node {
+                    Node::Atom(v) => write_json(v, out),
+                    _ => out.push_str("null"),
+                }
+            }
+            out.push(']');
+        }
+        Value::Pair(head, tail) => {
+            let nodes = vec![*head.clone(), *tail.clone()];
+            write_json(&Value::List(nodes), out)
+        }
+        Value::Map(map) => {
+            let mut keys: Vec<&MapKey> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                match key {
+                    MapKey::String(s) | MapKey::Keyword(s) => write_json_string(s, out),
+                    MapKey::Char(c) => write_json_string(&c.to_string(), out),
+                    MapKey::Integer(i) => write_json_string(&i.to_string(), out),
+                    MapKey::Number(n) => write_json_string(&n.to_string(), out),
+                    MapKey::Boolean(b) => write_json_string(&b.to_string(), out),
+                }
+                out.push(':');
+                write_json(&map[key], out);
+            }
+            out.push('}');
+        }
+        Value::Number(_) | Value::Function(_) | Value::Quoted(_) | Value::Null => {
+            out.push_str("null")
+        }
+    }
+}
+
+fn write_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// How deeply arrays and objects may nest, so that hostile input cannot
+/// exhaust the stack.
+const MAX_NESTING: usize = 128;
+
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+    /// Number of arrays and objects the parser is inside of.
+    depth: usize,
+}
+
+impl JsonParser {
+    fn error(&self, message: &str) -> InterpretatorError {
+        InterpretatorError::JsonError(format!("{} at character {}", message, self.pos))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), InterpretatorError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, InterpretatorError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c @ ('{' | '[')) => {
+                if self.depth == MAX_NESTING {
+                    return Err(self.error("nesting too deep"));
+                }
+                self.depth += 1;
+                let value = if c == '{' {
+                    self.parse_object()
+                } else {
+                    self.parse_array()
+                };
+                self.depth -= 1;
+                value
+            }
+            Some('"') => Ok(Value::String(self.parse_string()?)),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(_) => self.parse_literal(),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Value, InterpretatorError> {
+        for (word, value) in [
+            ("true", Value::Boolean(true)),
+            ("false", Value::Boolean(false)),
+            ("null", Value::Null),
+        ] {
+            let end = self.pos + word.len();
+            if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars())
+            {
+                self.pos = end;
+                return Ok(value);
+            }
+        }
+        Err(self.error("unexpected character"))
+    }
+
+    fn parse_number(&mut self) -> Result<Value, InterpretatorError> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        let integer_start = self.pos;
+        if !self.skip_digits() {
+            return Err(self.error("expected a digit"));
+        }
+        if self.chars[integer_start] == '0' && self.pos - integer_start > 1 {
+            self.pos = integer_start;
+            return Err(self.error("leading zero in number"));
+        }
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            if !self.skip_digits() {
+                return Err(self.error("expected a digit"));
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            if !self.skip_digits() {
+                return Err(self.error("expected a digit"));
+            }
+        }
+        let number: String = self.chars[start..self.pos].iter().collect();
+        // Integers too large for i64 are still read, as floats.
+        if let Ok(i) = number.parse::<i64>() {
+            return Ok(Value::Integer(i));
+        }
+        number
+            .parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    /// Skips a run of digits, returning false if there was none.
+    fn skip_digits(&mut self) -> bool {
+        let start = self.pos;
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn parse_string(&mut self) -> Result<String, InterpretatorError> {
+        self.expect('"')?;
+        let mut result = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(result),
+                Some('\\') => result.push(self.parse_escape()?),
+                Some(c) if (c as u32) < 0x20 => {
+                    self.pos -= 1;
+                    return Err(self.error("control character in string"));
+                }
+                Some(c) => result.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, InterpretatorError> {
+        match self.next() {
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('/') => Ok('/'),
+            Some('b') => Ok('\u{8}'),
+            Some('f') => Ok('\u{c}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('u') => {
+                let high = self.parse_hex4()?;
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    self.expect('\\')?;
+                    self.expect('u')?;
+                    let low = self.parse_hex4()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(self.error("invalid surrogate pair"));
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+            }
+            _ => Err(self.error("invalid escape")),
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, InterpretatorError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .peek()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("expected a hex digit"))?;
+            self.pos += 1;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn parse_array(&mut self) -> Result<Value, InterpretatorError> {
+        self.expect('[')?;
+        let mut values = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::List(values));
+        }
+        loop {
+            values.push(Node::Atom(self.parse_value()?));
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::List(values)),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected ',' or ']'"));
+                }
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value, InterpretatorError> {
+        self.expect('{')?;
+        let mut map = HashMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Map(map));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            map.insert(MapKey::String(key), self.parse_value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Map(map)),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected ',' or '}'"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::interpretator::Interpretator;
+    use super::*;
+
+    fn run(source: &str) -> Value {
+        Interpretator::new(None).run(source.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn test_to_json_scalars() {
+        assert_eq!(Value::Number(1.0).to_json(), "1.0");
+        assert_eq!(Value::Integer(1).to_json(), "1");
+        assert_eq!(Value::Number(1e21).to_json(), "1000000000000000000000.0");
+        assert_eq!(Value::Number(-2.5).to_json(), "-2.5");
+        assert_eq!(Value::Number(f64::NAN).to_json(), "null");
+        assert_eq!(Value::Boolean(false).to_json(), "false");
+        assert_eq!(run("{:b \\x 1 :y}").to_json(), "{\"1\":\"y\",\"b\":\"x\"}");
+        assert_eq!(Value::Null.to_json(), "null");
+        assert_eq!(Value::String("hi".to_string()).to_json(), "\"hi\"");
+    }
+
+    #[test]
+    fn test_to_json_string_escapes() {
+        assert_eq!(
+            Value::String("say \"hi\"\\\n\tnow\u{1}".to_string()).to_json(),
+            "\"say \\\"hi\\\"\\\\\\n\\tnow\\u0001\""
+        );
+        assert_eq!(Value::String("héllo".to_string()).to_json(), "\"héllo\"");
+    }
+
+    #[test]
+    fn test_to_json_nested() {
+        assert_eq!(
+            run("{\"b\" [1 [true nil]] \"a\" {\"c\" \"d\"}}").to_json(),
+            "{\"a\":{\"c\":\"d\"},\"b\":[1,[true,null]]}"
+        );
+        assert_eq!(run("[]").to_json(), "[]");
+        assert_eq!(run("{}").to_json(), "{}");
+    }
+
+    #[test]
+    fn test_to_json_functions() {
+        assert_eq!(run("[+ (fn [x] x)]").to_json(), "[null,null]");
+    }
+
+    #[test]
+    fn test_from_json_scalars() {
+        assert!(matches!(Value::from_json("1"), Ok(Value::Integer(1))));
+        assert!(matches!(Value::from_json("12"), Ok(Value::Integer(12))));
+        assert!(matches!(Value::from_json("12.0"), Ok(Value::Number(_))));
+        assert_eq!(Value::from_json(" -2.5e2 "), Ok(Value::Number(-250.0)));
+        assert_eq!(Value::from_json("true"), Ok(Value::Boolean(true)));
+        assert_eq!(Value::from_json("null"), Ok(Value::Null));
+        assert_eq!(
+            Value::from_json("\"a\\\"b\\n\\u00e9\\ud83d\\ude00\""),
+            Ok(Value::String("a\"b\né😀".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_from_json_nested() {
+        assert_eq!(
+            Value::from_json("{\"a\": [1, {\"b\": null}], \"c\": \"d\"}"),
+            Ok(run("{\"a\" [1 {\"b\" nil}] \"c\" \"d\"}"))
+        );
+        assert_eq!(Value::from_json("[ ]"), Ok(run("[]")));
+        assert_eq!(Value::from_json("{ }"), Ok(run("{}")));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let value = run("{\"name\" \"x\" \"items\" [1 2.5 [true false nil]] \"empty\" {}}");
+        assert_eq!(Value::from_json(&value.to_json()), Ok(value.clone()));
+        let whole = Value::Number(3.0);
+        assert!(matches!(
+            Value::from_json(&whole.to_json()),
+            Ok(Value::Number(n)) if n == 3.0
+        ));
+        let text = Value::String("say \"hi\"\\\n\t\u{1}".to_string());
+        assert_eq!(Value::from_json(&text.to_json()), Ok(text));
+    }
+
+    #[test]
+    fn test_from_json_nesting_limit() {
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(Value::from_json(&nested(MAX_NESTING)).is_ok());
+        assert_eq!(
+            Value::from_json(&nested(50000)).map_err(|e| e.to_string()),
+            Err(format!(
+                "JsonError: nesting too deep at character {}",
+                MAX_NESTING
+            ))
+        );
+        assert!(Value::from_json(&"{\"a\":".repeat(200)).is_err());
+    }
+
+    #[test]
+    fn test_from_json_invalid() {
+        for source in [
+            "",
+            "[1,]",
+            "[1 2]",
+            "{\"a\" 1}",
+            "{a: 1}",
+            "\"abc",
+            "tru",
+            "01x",
+            "-",
+            "1.",
+            "[1]]",
+            "\"\\x\"",
+            "\"a\nb\"",
+        ] {
+            assert!(
+                matches!(
+                    Value::from_json(source),
+                    Err(InterpretatorError::JsonError(_))
+                ),
+                "{:?} should be rejected",
+                source
+            );
+        }
+        assert_eq!(
+            Value::from_json("[1,]"),
+            Err(InterpretatorError::JsonError(
+                "unexpected character at character 3".to_string()
+            ))
+        );
+    }
+}
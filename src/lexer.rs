@@ -1,10 +1,27 @@
-use std::{error, fmt::Display};
+use std::{error, fmt::Display, io, io::Read, ops::Range, str};
 
 pub struct Lexer {
     input: String,
-    read_position: usize,
     ch: Option<char>,
+    /// Position of `ch` in the input.
+    position: Position,
+    /// Byte offset of `ch` in the input.
+    offset: usize,
 }
+
+/// A 1-based line and column in the source, counted in characters.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     OpenParen,
@@ -15,9 +32,18 @@ pub enum Token {
     CloseBrace,
     Dot,
     Identifier(String),
+    Integer(i64),
     Number(f64),
     String(String),
+    Char(char),
+    Keyword(String),
     Bool(bool),
+    Nil,
+    /// `'`, shorthand for quoting the next form.
+    Quote,
+    /// `@`, splicing the list the next form evaluates to into the
+    /// arguments of a call.
+    Splice,
     EOF,
 }
 
@@ -27,43 +53,109 @@ pub enum LexerError {
     InvalidIdentifier(String),
     InvalidNumber(String),
     UnclosedString(String),
+    InvalidChar(String),
 }
 impl Display for LexerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LexerError::InvalidCharacter(ch) => write!(f, "LexerError: Invalid char {}", ch),
+            LexerError::InvalidCharacter(ch) => {
+                write!(f, "LexerError: Invalid char {}", ch.escape_default())
+            }
             LexerError::InvalidIdentifier(ident) => write!(f, "LexerError: {}", ident),
             LexerError::InvalidNumber(number) => write!(f, "LexerError: {}", number),
             LexerError::UnclosedString(string) => write!(f, "LexerError: {}", string),
+            LexerError::InvalidChar(name) => {
+                write!(f, "LexerError: Invalid char literal \\{}", name)
+            }
         }
     }
 }
 impl error::Error for LexerError {}
 
+/// Control characters other than whitespace are not allowed in source,
+/// outside of comments.
+fn is_forbidden(c: char) -> bool {
+    c.is_control() && !c.is_whitespace()
+}
+
 impl Lexer {
     pub fn new(input: String) -> Lexer {
         let mut l = Lexer {
             input,
-            read_position: 0,
             ch: None,
+            position: Position { line: 1, column: 1 },
+            offset: 0,
         };
         l.read_char();
         l
     }
 
+    /// Creates a lexer over UTF-8 source read from `reader`. The input is
+    /// read in chunks, and a character split across two chunks is decoded
+    /// once both halves have arrived.
+    pub fn from_reader(reader: impl Read) -> io::Result<Lexer> {
+        Lexer::from_reader_with_chunk_size(reader, 8 * 1024)
+    }
+
+    fn from_reader_with_chunk_size(mut reader: impl Read, chunk_size: usize) -> io::Result<Lexer> {
+        let mut input = String::new();
+        let mut chunk = vec![0; chunk_size];
+        let mut pending = vec![];
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            pending.extend_from_slice(&chunk[..read]);
+            let valid = match str::from_utf8(&pending) {
+                Ok(s) => s.len(),
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            };
+            input.push_str(str::from_utf8(&pending[..valid]).unwrap());
+            pending.drain(..valid);
+        }
+        if !pending.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "source ends in the middle of a UTF-8 character",
+            ));
+        }
+        Ok(Lexer::new(input))
+    }
+
     pub fn read_char(&mut self) -> Option<char> {
-        if self.read_position >= self.input.len() {
-            self.ch = None;
-        } else {
-            self.ch = Some(self.input.chars().nth(self.read_position).unwrap());
+        if let Some(c) = self.ch {
+            self.offset += c.len_utf8();
         }
-        self.read_position += 1;
+        match self.ch {
+            Some('\n') => {
+                self.position.line += 1;
+                self.position.column = 1;
+            }
+            Some(_) => self.position.column += 1,
+            None => {}
+        }
+        self.ch = self.input[self.offset..].chars().next();
         self.ch
     }
 
+    fn peek_char(&self) -> Option<char> {
+        let c = self.ch?;
+        self.input[self.offset + c.len_utf8()..].chars().next()
+    }
+
+    /// Skips whitespace and comments, which run from `;` to the end of the
+    /// line.
     fn skip_whitespace(&mut self) {
         while let Some(c) = self.ch {
-            if c.is_whitespace() {
+            if c == ';' {
+                while !matches!(self.ch, Some('\n') | None) {
+                    self.read_char();
+                }
+            } else if c.is_whitespace() {
                 self.read_char();
             } else {
                 break;
@@ -71,10 +163,13 @@ impl Lexer {
         }
     }
 
+    /// Reads an identifier, which runs until whitespace or a language
+    /// symbol. Operator characters are ordinary identifier characters, so
+    /// names such as `<=`, `->` and `empty?` are read whole.
     fn read_identifier(&mut self) -> Result<String, LexerError> {
         let mut result = String::new();
         while let Some(c) = self.ch {
-            if !c.is_whitespace() && !self.is_language_symbol(c) {
+            if !c.is_whitespace() && !self.is_language_symbol(c) && !is_forbidden(c) {
                 result.push(c);
                 self.read_char();
             } else {
@@ -84,18 +179,29 @@ impl Lexer {
         Ok(result)
     }
 
-    fn read_number(&mut self) -> Result<f64, LexerError> {
-        let mut result = String::new();
+    /// Reads a number, appending to `result`, which holds any sign already
+    /// read. Numbers without a `.` or an exponent are integers.
+    fn read_number(&mut self, mut result: String) -> Result<Token, LexerError> {
         while let Some(c) = self.ch {
-            if c.is_whitespace() || (self.is_language_symbol(c) && c != '.') {
+            if c.is_whitespace() || (self.is_language_symbol(c) && c != '.') || is_forbidden(c) {
                 break;
             } else {
                 result.push(c);
                 self.read_char();
             }
         }
+        let digits = result.strip_prefix('-').unwrap_or(&result);
+        if digits.chars().all(|c| c.is_ascii_digit()) {
+            return match result.parse::<i64>() {
+                Ok(i) => Ok(Token::Integer(i)),
+                Err(_) => Err(LexerError::InvalidNumber(format!(
+                    "Integer out of range : {}",
+                    result,
+                ))),
+            };
+        }
         match result.parse::<f64>() {
-            Ok(n) => Ok(n),
+            Ok(n) => Ok(Token::Number(n)),
             Err(_) => Err(LexerError::InvalidNumber(format!(
                 "Error parsing number : {}",
                 result,
@@ -110,24 +216,54 @@ impl Lexer {
             if c == '"' {
                 self.read_char();
                 return Ok(result);
+            } else if is_forbidden(c) {
+                return Err(LexerError::InvalidCharacter(c));
             } else {
                 result.push(c);
                 self.read_char();
             }
         }
-        return Err(LexerError::UnclosedString(format!(
+        Err(LexerError::UnclosedString(format!(
             "Unclosed string : {}",
             result
-        )));
+        )))
     }
 
-    fn is_language_symbol(&self, c: char) -> bool {
-        match c {
-            '(' | ')' | '[' | ']' | '{' | '}' | '.' => true,
-            _ => false,
+    /// Reads a char literal after its backslash: `\a` for a single
+    /// character, or `\space`, `\newline` and `\tab` for whitespace.
+    fn read_char_literal(&mut self) -> Result<char, LexerError> {
+        let c = match self.ch {
+            Some(c) if is_forbidden(c) => return Err(LexerError::InvalidCharacter(c)),
+            Some(c) if !c.is_whitespace() => c,
+            _ => return Err(LexerError::InvalidChar(String::new())),
+        };
+        self.read_char();
+        if !c.is_alphabetic() {
+            return Ok(c);
+        }
+        let mut name = c.to_string();
+        while let Some(c) = self.ch {
+            if c.is_whitespace() || self.is_language_symbol(c) || is_forbidden(c) {
+                break;
+            }
+            name.push(c);
+            self.read_char();
+        }
+        match name.as_str() {
+            "space" => Ok(' '),
+            "newline" => Ok('\n'),
+            "tab" => Ok('\t'),
+            _ if name.chars().count() == 1 => Ok(c),
+            _ => Err(LexerError::InvalidChar(name)),
         }
     }
 
+    /// Characters that end an identifier or number and form tokens of
+    /// their own.
+    fn is_language_symbol(&self, c: char) -> bool {
+        matches!(c, '(' | ')' | '[' | ']' | '{' | '}' | '.' | ';')
+    }
+
     fn next_token(&mut self) -> Result<Token, LexerError> {
         self.skip_whitespace();
         let ch = match self.ch {
@@ -138,6 +274,7 @@ impl Lexer {
         };
 
         match ch {
+            c if is_forbidden(c) => Err(LexerError::InvalidCharacter(c)),
             '{' => {
                 self.read_char();
                 Ok(Token::OpenBrace)
@@ -166,12 +303,23 @@ impl Lexer {
                 self.read_char();
                 Ok(Token::Dot)
             }
-            '-' => {
+            '\'' => {
                 self.read_char();
-                match self.read_number() {
-                    Ok(n) => Ok(Token::Number(-n)),
-                    Err(e) => Err(e),
-                }
+                Ok(Token::Quote)
+            }
+            '@' => {
+                self.read_char();
+                Ok(Token::Splice)
+            }
+            '\\' => {
+                self.read_char();
+                Ok(Token::Char(self.read_char_literal()?))
+            }
+            // `-` only starts a number when a digit follows it, otherwise it
+            // starts an identifier such as the subtraction operator.
+            '-' if self.peek_char().is_some_and(|c| c.is_ascii_digit()) => {
+                self.read_char();
+                self.read_number(String::from("-"))
             }
             '"' => {
                 let s = self.read_string();
@@ -182,11 +330,7 @@ impl Lexer {
             }
             _ => {
                 if ch.is_numeric() {
-                    let n = self.read_number();
-                    match n {
-                        Ok(n) => Ok(Token::Number(n)),
-                        Err(e) => Err(e),
-                    }
+                    self.read_number(String::new())
                 } else {
                     let ident = self.read_identifier();
                     match ident {
@@ -195,6 +339,10 @@ impl Lexer {
                                 Ok(Token::Bool(true))
                             } else if ident == "false" {
                                 Ok(Token::Bool(false))
+                            } else if ident == "nil" {
+                                Ok(Token::Nil)
+                            } else if ident.len() > 1 && ident.starts_with(':') {
+                                Ok(Token::Keyword(ident[1..].to_string()))
                             } else {
                                 Ok(Token::Identifier(ident))
                             }
@@ -207,18 +355,43 @@ impl Lexer {
     }
 
     pub fn tokenize(&mut self) -> Result<Vec<Token>, LexerError> {
+        Ok(self
+            .tokenize_with_positions()?
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect())
+    }
+
+    /// Like `tokenize`, but pairs every token with the position of its
+    /// first character.
+    pub fn tokenize_with_positions(&mut self) -> Result<Vec<(Token, Position)>, LexerError> {
         let mut tokens = Vec::new();
         loop {
-            let tok = self.next_token();
-            match tok {
-                Ok(tok) => {
-                    if tok == Token::EOF {
-                        tokens.push(tok);
-                        break;
-                    }
-                    tokens.push(tok)
-                }
-                Err(e) => return Err(e),
+            self.skip_whitespace();
+            let position = self.position;
+            let tok = self.next_token()?;
+            if tok == Token::EOF {
+                tokens.push((tok, position));
+                break;
+            }
+            tokens.push((tok, position));
+        }
+        Ok(tokens)
+    }
+
+    /// Like `tokenize`, but pairs every token with the byte range of the
+    /// input it was read from, so `&input[range]` is its source text.
+    /// Whitespace and comments belong to no token.
+    pub fn tokenize_with_spans(&mut self) -> Result<Vec<(Token, Range<usize>)>, LexerError> {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let start = self.offset;
+            let tok = self.next_token()?;
+            let eof = tok == Token::EOF;
+            tokens.push((tok, start..self.offset));
+            if eof {
+                break;
             }
         }
         Ok(tokens)
@@ -237,7 +410,7 @@ mod tests {
         assert_eq!(l.next_token(), Ok(Token::OpenParen));
         assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("+"))));
         assert_eq!(l.next_token(), Ok(Token::Number(-1.2)));
-        assert_eq!(l.next_token(), Ok(Token::Number(2.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(2)));
         assert_eq!(l.next_token(), Ok(Token::CloseParen));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
@@ -257,6 +430,15 @@ mod tests {
         assert!(matches!(l.next_token(), Err(LexerError::UnclosedString(_))));
     }
 
+    #[test]
+    fn test_lexer_unicode_string() {
+        let input = String::from("\"日本語\" x");
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Ok(Token::String(String::from("日本語"))));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("x"))));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
     #[test]
     fn test_lexer_number() {
         let input = String::from("-1.2");
@@ -289,7 +471,7 @@ mod tests {
         let input = String::from("hello 1");
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("hello"))));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
 
@@ -298,7 +480,7 @@ mod tests {
         let input = String::from("hello 1");
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("hello"))));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
 
@@ -307,8 +489,8 @@ mod tests {
         let input = String::from("hello 1 2");
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("hello"))));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
-        assert_eq!(l.next_token(), Ok(Token::Number(2.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(2)));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
 
@@ -318,8 +500,8 @@ mod tests {
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::OpenParen));
         assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("+"))));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
-        assert_eq!(l.next_token(), Ok(Token::Number(2.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(2)));
         assert_eq!(l.next_token(), Ok(Token::CloseParen));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
@@ -330,8 +512,8 @@ mod tests {
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::OpenParen));
         assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("+"))));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
-        assert_eq!(l.next_token(), Ok(Token::Number(2.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(2)));
         assert_eq!(l.next_token(), Ok(Token::CloseParen));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
@@ -342,10 +524,10 @@ mod tests {
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::OpenParen));
         assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("+"))));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
-        assert_eq!(l.next_token(), Ok(Token::Number(2.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(2)));
         assert_eq!(l.next_token(), Ok(Token::CloseParen));
-        assert_eq!(l.next_token(), Ok(Token::Number(3.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(3)));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
 
@@ -354,8 +536,8 @@ mod tests {
         let input = String::from("[1 2]");
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::OpenBracket));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
-        assert_eq!(l.next_token(), Ok(Token::Number(2.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(2)));
         assert_eq!(l.next_token(), Ok(Token::CloseBracket));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
@@ -397,7 +579,7 @@ mod tests {
         let input = String::from("{ 1 }");
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::OpenBrace));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
         assert_eq!(l.next_token(), Ok(Token::CloseBrace));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
@@ -407,8 +589,8 @@ mod tests {
         let input = String::from("{ 1 2 }");
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::OpenBrace));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
-        assert_eq!(l.next_token(), Ok(Token::Number(2.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(2)));
         assert_eq!(l.next_token(), Ok(Token::CloseBrace));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
@@ -418,7 +600,7 @@ mod tests {
         let input = String::from("{1 \"Hello\"}");
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::OpenBrace));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
         assert_eq!(l.next_token(), Ok(Token::String(String::from("Hello"))));
         assert_eq!(l.next_token(), Ok(Token::CloseBrace));
         assert_eq!(l.next_token(), Ok(Token::EOF));
@@ -429,7 +611,7 @@ mod tests {
         let input = String::from("{1 Hello}");
         let mut l = Lexer::new(input);
         assert_eq!(l.next_token(), Ok(Token::OpenBrace));
-        assert_eq!(l.next_token(), Ok(Token::Number(1.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
         assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("Hello"))));
         assert_eq!(l.next_token(), Ok(Token::CloseBrace));
         assert_eq!(l.next_token(), Ok(Token::EOF));
@@ -446,4 +628,287 @@ mod tests {
         assert_eq!(l.next_token(), Ok(Token::CloseParen));
         assert_eq!(l.next_token(), Ok(Token::EOF));
     }
+
+    #[test]
+    fn test_lexer_minus_operator() {
+        let input = String::from("(- 5 3)");
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Ok(Token::OpenParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("-"))));
+        assert_eq!(l.next_token(), Ok(Token::Integer(5)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(3)));
+        assert_eq!(l.next_token(), Ok(Token::CloseParen));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_minus_before_delimiter() {
+        let input = String::from("(apply -)");
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Ok(Token::OpenParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("apply"))));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("-"))));
+        assert_eq!(l.next_token(), Ok(Token::CloseParen));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_minus_identifier() {
+        let input = String::from("-> -x");
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("->"))));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("-x"))));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_negative_number() {
+        let input = String::from("-5 (f -0.5)");
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Ok(Token::Integer(-5)));
+        assert_eq!(l.next_token(), Ok(Token::OpenParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("f"))));
+        assert_eq!(l.next_token(), Ok(Token::Number(-0.5)));
+        assert_eq!(l.next_token(), Ok(Token::CloseParen));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_from_reader() {
+        let source = "(defn greet [name] (str \"héllo, ✓ \" name)) (greet \"ünï\")";
+        let expected = Lexer::new(source.to_string()).tokenize();
+        for chunk_size in [1, 2, 3, 5, 8 * 1024] {
+            let reader = io::Cursor::new(source.as_bytes().to_vec());
+            let mut l = Lexer::from_reader_with_chunk_size(reader, chunk_size).unwrap();
+            assert_eq!(l.tokenize(), expected);
+        }
+        let reader = io::Cursor::new(source.as_bytes().to_vec());
+        assert_eq!(Lexer::from_reader(reader).unwrap().tokenize(), expected);
+    }
+
+    #[test]
+    fn test_lexer_from_reader_invalid_utf8() {
+        let reader = io::Cursor::new(vec![b'(', 0xff, b')']);
+        let err = Lexer::from_reader_with_chunk_size(reader, 1).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let reader = io::Cursor::new("\"é".as_bytes()[..2].to_vec());
+        let err = Lexer::from_reader(reader).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_lexer_positions() {
+        let input = String::from("(foo \"a b\"\n  [1 -2])");
+        let mut l = Lexer::new(input);
+        let at = |line, column| Position { line, column };
+        assert_eq!(
+            l.tokenize_with_positions(),
+            Ok(vec![
+                (Token::OpenParen, at(1, 1)),
+                (Token::Identifier(String::from("foo")), at(1, 2)),
+                (Token::String(String::from("a b")), at(1, 6)),
+                (Token::OpenBracket, at(2, 3)),
+                (Token::Integer(1), at(2, 4)),
+                (Token::Integer(-2), at(2, 6)),
+                (Token::CloseBracket, at(2, 8)),
+                (Token::CloseParen, at(2, 9)),
+                (Token::EOF, at(2, 10)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_lexer_quote_and_nil() {
+        let input = String::from("'(a nil) it's nils");
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Ok(Token::Quote));
+        assert_eq!(l.next_token(), Ok(Token::OpenParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("a"))));
+        assert_eq!(l.next_token(), Ok(Token::Nil));
+        assert_eq!(l.next_token(), Ok(Token::CloseParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("it's"))));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("nils"))));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_splice() {
+        let mut l = Lexer::new(String::from("(f @xs a@b)"));
+        assert_eq!(l.next_token(), Ok(Token::OpenParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("f"))));
+        assert_eq!(l.next_token(), Ok(Token::Splice));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("xs"))));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("a@b"))));
+        assert_eq!(l.next_token(), Ok(Token::CloseParen));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_operator_identifiers() {
+        let input = String::from("+ - * / < <= >= -> list->vec map! foo? a-b");
+        let mut l = Lexer::new(input);
+        for ident in [
+            "+",
+            "-",
+            "*",
+            "/",
+            "<",
+            "<=",
+            ">=",
+            "->",
+            "list->vec",
+            "map!",
+            "foo?",
+            "a-b",
+        ] {
+            assert_eq!(l.next_token(), Ok(Token::Identifier(String::from(ident))));
+        }
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_identifier_followed_by_delimiter() {
+        let input = String::from("f(<=[x]empty?{");
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("f"))));
+        assert_eq!(l.next_token(), Ok(Token::OpenParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("<="))));
+        assert_eq!(l.next_token(), Ok(Token::OpenBracket));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("x"))));
+        assert_eq!(l.next_token(), Ok(Token::CloseBracket));
+        assert_eq!(
+            l.next_token(),
+            Ok(Token::Identifier(String::from("empty?")))
+        );
+        assert_eq!(l.next_token(), Ok(Token::OpenBrace));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_char() {
+        let input = String::from("\\a \\Z(\\( \\\\ \\é \\space \\newline \\tab)");
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Ok(Token::Char('a')));
+        assert_eq!(l.next_token(), Ok(Token::Char('Z')));
+        assert_eq!(l.next_token(), Ok(Token::OpenParen));
+        assert_eq!(l.next_token(), Ok(Token::Char('(')));
+        assert_eq!(l.next_token(), Ok(Token::Char('\\')));
+        assert_eq!(l.next_token(), Ok(Token::Char('é')));
+        assert_eq!(l.next_token(), Ok(Token::Char(' ')));
+        assert_eq!(l.next_token(), Ok(Token::Char('\n')));
+        assert_eq!(l.next_token(), Ok(Token::Char('\t')));
+        assert_eq!(l.next_token(), Ok(Token::CloseParen));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_invalid_char() {
+        let mut l = Lexer::new(String::from("\\ab"));
+        assert_eq!(
+            l.next_token(),
+            Err(LexerError::InvalidChar(String::from("ab")))
+        );
+        let mut l = Lexer::new(String::from("\\"));
+        assert_eq!(l.next_token(), Err(LexerError::InvalidChar(String::new())));
+    }
+
+    #[test]
+    fn test_lexer_integers_and_floats() {
+        let mut l = Lexer::new(String::from("3 3.0 -7 1e3 9007199254740993"));
+        assert_eq!(l.next_token(), Ok(Token::Integer(3)));
+        assert_eq!(l.next_token(), Ok(Token::Number(3.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(-7)));
+        assert_eq!(l.next_token(), Ok(Token::Number(1000.0)));
+        assert_eq!(l.next_token(), Ok(Token::Integer(9007199254740993)));
+        let mut l = Lexer::new(String::from("99999999999999999999"));
+        assert!(l.next_token().is_err());
+    }
+
+    #[test]
+    fn test_lexer_keyword() {
+        let mut l = Lexer::new(String::from("{:a 1 :long-name} :"));
+        assert_eq!(l.next_token(), Ok(Token::OpenBrace));
+        assert_eq!(l.next_token(), Ok(Token::Keyword(String::from("a"))));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
+        assert_eq!(
+            l.next_token(),
+            Ok(Token::Keyword(String::from("long-name")))
+        );
+        assert_eq!(l.next_token(), Ok(Token::CloseBrace));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from(":"))));
+    }
+
+    #[test]
+    fn test_lexer_comments() {
+        let mut l = Lexer::new(String::from("; header\n(f 1) ; call f\nx;y\n\";\""));
+        assert_eq!(l.next_token(), Ok(Token::OpenParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("f"))));
+        assert_eq!(l.next_token(), Ok(Token::Integer(1)));
+        assert_eq!(l.next_token(), Ok(Token::CloseParen));
+        assert_eq!(l.next_token(), Ok(Token::Identifier(String::from("x"))));
+        assert_eq!(l.next_token(), Ok(Token::String(String::from(";"))));
+        assert_eq!(l.next_token(), Ok(Token::EOF));
+    }
+
+    #[test]
+    fn test_lexer_spans() {
+        let input = "(café \"a b\" ; note\n  [1.5 :k \\space 'x])";
+        let tokens = Lexer::new(input.to_string()).tokenize_with_spans().unwrap();
+        let slices: Vec<&str> = tokens
+            .iter()
+            .map(|(_, span)| &input[span.clone()])
+            .collect();
+        assert_eq!(
+            slices,
+            vec!["(", "café", "\"a b\"", "[", "1.5", ":k", "\\space", "'", "x", "]", ")", ""]
+        );
+        assert_eq!(tokens[1], (Token::Identifier(String::from("café")), 1..6));
+        assert_eq!(tokens.last(), Some(&(Token::EOF, input.len()..input.len())));
+    }
+
+    #[test]
+    fn test_lexer_control_chars() {
+        let invalid = |input: &str| Lexer::new(input.to_string()).tokenize();
+        assert_eq!(invalid("\0"), Err(LexerError::InvalidCharacter('\0')));
+        assert_eq!(invalid("ab\0cd"), Err(LexerError::InvalidCharacter('\0')));
+        assert_eq!(invalid("12\x07"), Err(LexerError::InvalidCharacter('\x07')));
+        assert_eq!(
+            invalid("\"a\x1bb\""),
+            Err(LexerError::InvalidCharacter('\x1b'))
+        );
+        assert_eq!(invalid("\\\x7f"), Err(LexerError::InvalidCharacter('\x7f')));
+        assert_eq!(
+            invalid("\\a\x01"),
+            Err(LexerError::InvalidCharacter('\x01'))
+        );
+        assert_eq!(
+            LexerError::InvalidCharacter('\0').to_string(),
+            "LexerError: Invalid char \\u{0}"
+        );
+        assert_eq!(
+            invalid("(a\t\r\n b) ; \0 in a comment"),
+            Ok(vec![
+                Token::OpenParen,
+                Token::Identifier(String::from("a")),
+                Token::Identifier(String::from("b")),
+                Token::CloseParen,
+                Token::EOF,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_lexer_arbitrary_input_terminates() {
+        let pieces = [
+            "", "(", "\"", "\\", "-", "1", ".", ":", ";", "a", "'", "\u{85}",
+        ];
+        for c in (0..=0x2000u32).filter_map(char::from_u32) {
+            for before in pieces.iter() {
+                for after in pieces.iter() {
+                    let input = format!("{}{}{}", before, c, after);
+                    let _ = Lexer::new(input).tokenize_with_spans();
+                }
+            }
+        }
+    }
 }
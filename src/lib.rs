@@ -0,0 +1,8 @@
+pub mod interpretator;
+pub mod json;
+pub mod lexer;
+pub mod nodes;
+pub mod parser;
+mod prelude;
+pub mod repl;
+mod special_forms;
@@ -1,22 +1,12 @@
-use std::{fs::read, io::stdin};
-
-//import lexer
-
-mod interpretator;
-mod lexer;
-mod nodes;
-mod parser;
+use lisp_rust::interpretator::Interpretator;
+use lisp_rust::repl::Repl;
+use std::io::{stdin, stdout, BufReader};
 
 fn main() {
-    let mut interpretator = interpretator::Interpretator::new(None);
-    loop {
-        let mut buffer = String::new();
-        stdin().read_line(&mut buffer).unwrap();
-        let source = buffer.trim();
-        let result: String = match interpretator.run(source.to_owned()) {
-            Ok(v) => format!("{:?}", v),
-            Err(e) => format!("{}", e),
-        };
-        println!("{}", result);
-    }
+    let args = std::env::args().skip(1).collect();
+    let mut repl = Repl::new(Interpretator::new(None).with_args(args));
+    // Read through a one byte buffer so `read-line` in evaluated code sees
+    // the lines that follow it.
+    let input = BufReader::with_capacity(1, stdin());
+    repl.run(input, stdout()).unwrap();
 }
@@ -1,126 +1,1139 @@
-use super::interpretator::Scope;
+use super::interpretator::{Context, InterpretatorError, Scope};
+use super::special_forms::{self, SpecialForm};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 #[allow(dead_code)]
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Value {
+    Integer(i64),
+    /// A floating point number.
     Number(f64),
     String(String),
+    Char(char),
     Boolean(bool),
+    /// A name that evaluates to itself, written `:name`.
+    Keyword(String),
+    /// A name as a value, as produced by quoting an identifier.
+    Symbol(String),
     List(Vec<Node>),
-    Map(HashMap<String, Value>),
+    /// A cons cell, written `(head . tail)`.
+    Pair(Box<Node>, Box<Node>),
+    Map(HashMap<MapKey, Value>),
     Function(Function),
+    /// An unevaluated form, as produced by `quote`.
+    Quoted(Box<Node>),
     Null,
 }
 
+/// Integers and floats compare by value, so `3` equals `3.0`. Any other
+/// value only equals values of the same type.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Integer(i), Value::Number(n)) | (Value::Number(n), Value::Integer(i)) => {
+                integer_equals_float(*i, *n)
+            }
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Char(a), Value::Char(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Keyword(a), Value::Keyword(b)) => a == b,
+            (Value::Symbol(a), Value::Symbol(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Pair(a, b), Value::Pair(c, d)) => a == c && b == d,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::Quoted(a), Value::Quoted(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            _ => false,
+        }
+    }
+}
+
+fn integer_equals_float(i: i64, n: f64) -> bool {
+    compare_integer_float(i, n) == Some(Ordering::Equal)
+}
+
+/// Orders an integer against a float exactly. Converting the integer to a
+/// float first would round large integers, so that `2^53 + 1` and `2^53`
+/// would both equal the float `2^53`.
+fn compare_integer_float(i: i64, n: f64) -> Option<Ordering> {
+    // -2^63 and 2^63 are exact floats, and the integers lie in between.
+    const BOUND: f64 = 9_223_372_036_854_775_808.0;
+    if n.is_nan() {
+        None
+    } else if n >= BOUND {
+        Some(Ordering::Less)
+    } else if n < -BOUND {
+        Some(Ordering::Greater)
+    } else {
+        let whole = n.trunc();
+        match i.cmp(&(whole as i64)) {
+            Ordering::Equal => 0.0.partial_cmp(&(n - whole)),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+/// The values maps can be keyed by. Floats with an integer value are
+/// stored as integers, so `3` and `3.0` are the same key. Floats are never
+/// NaN, and negative zero is stored as zero, so equal keys hash alike.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapKey {
+    Integer(i64),
+    Number(f64),
+    String(String),
+    Char(char),
+    Boolean(bool),
+    Keyword(String),
+}
+
+impl Eq for MapKey {}
+
+impl Hash for MapKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            MapKey::Integer(i) => i.hash(state),
+            MapKey::Number(n) => n.to_bits().hash(state),
+            MapKey::String(s) | MapKey::Keyword(s) => s.hash(state),
+            MapKey::Char(c) => c.hash(state),
+            MapKey::Boolean(b) => b.hash(state),
+        }
+    }
+}
+
+impl MapKey {
+    fn rank(&self) -> u8 {
+        match self {
+            MapKey::Integer(_) | MapKey::Number(_) => 0,
+            MapKey::String(_) => 1,
+            MapKey::Char(_) => 2,
+            MapKey::Boolean(_) => 3,
+            MapKey::Keyword(_) => 4,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Integer(i) => Value::Integer(*i),
+            MapKey::Number(n) => Value::Number(*n),
+            MapKey::String(s) => Value::String(s.clone()),
+            MapKey::Char(c) => Value::Char(*c),
+            MapKey::Boolean(b) => Value::Boolean(*b),
+            MapKey::Keyword(k) => Value::Keyword(k.clone()),
+        }
+    }
+}
+
+/// Keys of different types are ordered numbers, strings, chars, booleans,
+/// then keywords, so maps can be written in a stable order.
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MapKey::Integer(a), MapKey::Integer(b)) => a.cmp(b),
+            (MapKey::Number(a), MapKey::Number(b)) => a.total_cmp(b),
+            (MapKey::Integer(a), MapKey::Number(b)) => {
+                (*a as f64).total_cmp(b).then(Ordering::Less)
+            }
+            (MapKey::Number(a), MapKey::Integer(b)) => {
+                a.total_cmp(&(*b as f64)).then(Ordering::Greater)
+            }
+            (MapKey::String(a), MapKey::String(b)) => a.cmp(b),
+            (MapKey::Char(a), MapKey::Char(b)) => a.cmp(b),
+            (MapKey::Boolean(a), MapKey::Boolean(b)) => a.cmp(b),
+            (MapKey::Keyword(a), MapKey::Keyword(b)) => a.cmp(b),
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Function {
     Native(NativeFunction),
     UserDefined(UserDefinedFunction),
+    /// Called with its argument forms unevaluated, returning the form to
+    /// evaluate in their place.
+    Macro(UserDefinedFunction),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct UserDefinedFunction {
     pub args: Vec<String>,
-    pub body: Vec<Node>,
+    /// Shared, so that looking a function up does not copy its body.
+    pub body: Rc<[Node]>,
+    /// The scope the function was defined in, which its body sees.
+    pub scope: Scope,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A function is only equal to itself and its copies, which share its body.
+/// Two lambdas written the same way are different functions.
+impl PartialEq for UserDefinedFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.body, &other.body)
+    }
+}
+
+/// The Rust side of a native function. It may capture values, as the
+/// functions returned by `comp` do.
+pub type NativeFn = Rc<dyn Fn(Vec<Value>, &Scope, &mut Context) -> Result<Value, String>>;
+
+#[derive(Clone)]
 pub struct NativeFunction {
     pub name: String,
     pub args: Vec<String>,
-    pub func: fn(Vec<Value>) -> Result<Value, String>,
+    pub func: NativeFn,
+}
+
+/// Natives are the same function only if they share their implementation.
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.args == other.args && Rc::ptr_eq(&self.func, &other.func)
+    }
 }
 
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("args", &self.args)
+            .finish()
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Node {
     Atom(Value),
     FunctionCall(String, Vec<Node>),
+    Map(Vec<(Node, Node)>),
     Program(Vec<Node>),
     Variable(String),
+    /// `@form` in the arguments of a call, which passes the elements of the
+    /// list `form` evaluates to as separate arguments.
+    Splice(Box<Node>),
     EOF,
 }
 
 impl Node {
-    pub fn evaluate(&self, scope: &Scope) -> Result<Value, String> {
+    pub fn evaluate(&self, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+        // Calls are traced by `evaluate_tail`, which every call goes through.
+        if !matches!(self, Node::FunctionCall(_, _)) {
+            self.trace(ctx);
+        }
         match self {
-            Node::Atom(v) => Ok(v.clone()),
-            Node::FunctionCall(name, args) => {
-                let func = match scope.get(&name) {
-                    Some(v) => v,
-                    None => return Err(format!("{} is not defined", name)),
-                };
-
-                match func {
-                    Value::Function(f) => {
-                        let mut new_scope = Scope::new(Some(scope));
-                        let arg_names = match f {
-                            Function::UserDefined(f) => f.args.clone(),
-                            Function::Native(f) => f.args.clone(),
-                        };
-
-                        if args.len() != arg_names.len() {
-                            return Err(format!(
-                                "Function {} takes {} arguments, but {} were given",
-                                name,
-                                arg_names.len(),
-                                args.len()
-                            ));
-                        }
-                        let mut evaluated_args = vec![];
-                        for (i, arg) in args.iter().enumerate() {
-                            let arg_val = arg.evaluate(scope)?;
-                            new_scope.set(arg_names[i].clone(), arg_val.clone());
-                            evaluated_args.push(arg_val);
-                        }
-
-                        let result = match f {
-                            Function::UserDefined(f) => {
-                                let new_scope = Scope::new(Some(&new_scope));
-                                let mut result = Ok(Value::Null);
-                                for node in &f.body {
-                                    result = node.evaluate(&new_scope);
-                                }
+            Node::Atom(v) => v.evaluate(scope, ctx),
+            Node::FunctionCall(_, _) => match self.evaluate_tail(scope, ctx)? {
+                Tail::Value(v) => Ok(v),
+                Tail::Call(name, f, args) => f.call(&name, args, scope, ctx),
+            },
+            Node::Map(entries) => evaluate_map(entries, scope, ctx),
+            Node::Program(nodes) => {
+                let mut result = Value::Null;
+                for node in nodes.iter().filter(|n| !matches!(n, Node::EOF)) {
+                    result = node.evaluate(scope, ctx)?;
+                }
+                Ok(result)
+            }
+            Node::Variable(name) => match scope.get(name) {
+                Some(v) => Ok(v),
+                None => Err(format!("{} is not defined", name)),
+            },
+            Node::Splice(_) => Err(format!(
+                "{} can only be used in the arguments of a call",
+                self
+            )),
+            Node::EOF => Ok(Value::Null),
+        }
+    }
+}
+
+fn evaluate_map(
+    entries: &[(Node, Node)],
+    scope: &Scope,
+    ctx: &mut Context,
+) -> Result<Value, String> {
+    let mut map = HashMap::new();
+    for (key, value) in entries {
+        let key = key.evaluate(scope, ctx)?.to_map_key()?;
+        map.insert(key, value.evaluate(scope, ctx)?);
+    }
+    Ok(Value::Map(map))
+}
 
-                                return result;
+/// The result of evaluating a form in tail position.
+enum Tail {
+    Value(Value),
+    /// A call that still has to be performed, with its evaluated arguments.
+    Call(String, Function, Vec<Value>),
+}
+
+impl Node {
+    /// Passes the node to the trace hook, if one is set.
+    fn trace(&self, ctx: &mut Context) {
+        if let Some(trace) = ctx.trace.as_mut() {
+            trace(self, ctx.depth);
+        }
+    }
+
+    /// Evaluates a form in tail position. A function call is returned to
+    /// the caller instead of being performed, so that a function body
+    /// ending in a call can loop instead of recursing.
+    fn evaluate_tail(&self, scope: &Scope, ctx: &mut Context) -> Result<Tail, String> {
+        let (name, args) = match self {
+            Node::FunctionCall(name, args) => (name, args),
+            node => return node.evaluate(scope, ctx).map(Tail::Value),
+        };
+        self.trace(ctx);
+
+        match special_forms::lookup(name) {
+            Some(SpecialForm::Value(form)) => return form(args, scope, ctx).map(Tail::Value),
+            Some(SpecialForm::Tail(form)) => {
+                return match form(args, scope, ctx)? {
+                    Some(node) => node.evaluate_tail(scope, ctx),
+                    None => Ok(Tail::Value(Value::Null)),
+                }
+            }
+            None => {}
+        }
+
+        let func = match scope.get(name) {
+            Some(v) => v,
+            None => return Err(format!("{} is not defined", name)),
+        };
+
+        match func {
+            Value::Function(Function::Macro(m)) => {
+                let expansion = expand_macro(m, name, args, scope, ctx)?;
+                // An expansion can contain another macro call, so expanding
+                // counts against the depth limit like a call does.
+                if ctx.depth >= ctx.max_depth {
+                    return Err("recursion limit exceeded".to_string());
+                }
+                ctx.depth += 1;
+                let result = expansion.evaluate_tail(scope, ctx);
+                ctx.depth -= 1;
+                result
+            }
+            Value::Function(f) => {
+                let mut evaluated_args = vec![];
+                for arg in args {
+                    match arg {
+                        Node::Splice(list) => match list.evaluate(scope, ctx)? {
+                            Value::List(nodes) => {
+                                for node in nodes {
+                                    evaluated_args.push(match node {
+                                        Node::Atom(v) => v,
+                                        node => node.evaluate(scope, ctx)?,
+                                    });
+                                }
+                            }
+                            v => {
+                                return Err(format!(
+                                    "cannot splice {}: expected a list, got {} {}",
+                                    list,
+                                    v.type_name(),
+                                    v
+                                ))
                             }
-                            Function::Native(f) => (f.func)(evaluated_args),
-                        };
-                        result
+                        },
+                        arg => evaluated_args.push(arg.evaluate(scope, ctx)?),
                     }
-                    _ => Err(format!("{} is not a function", name)),
                 }
+                Ok(Tail::Call(name.clone(), f, evaluated_args))
             }
-            Node::Program(nodes) => {
-                let mut result = Value::Null;
-                for node in nodes {
-                    result = node.evaluate(scope)?;
+            v => Err(format!(
+                "cannot call {}: expected a function, got {} {}",
+                name,
+                v.type_name(),
+                v
+            )),
+        }
+    }
+}
+
+impl Function {
+    /// Calls the function with already evaluated arguments. `name` is only
+    /// used in error messages and `scope` is the caller's scope.
+    pub fn call(
+        &self,
+        name: &str,
+        args: Vec<Value>,
+        scope: &Scope,
+        ctx: &mut Context,
+    ) -> Result<Value, String> {
+        if ctx.depth >= ctx.max_depth {
+            return Err("recursion limit exceeded".to_string());
+        }
+        ctx.depth += 1;
+        let result = self.clone().run(name.to_string(), args, scope, ctx);
+        ctx.depth -= 1;
+        result
+    }
+
+    /// Runs the function, performing calls in tail position of a user
+    /// defined body in a loop rather than recursively.
+    fn run(
+        self,
+        name: String,
+        args: Vec<Value>,
+        scope: &Scope,
+        ctx: &mut Context,
+    ) -> Result<Value, String> {
+        let (mut name, mut function, mut args) = (name, self, args);
+        loop {
+            let f = match &function {
+                Function::UserDefined(f) => f,
+                Function::Macro(_) => {
+                    return Err(format!("{} is a macro and cannot be called", name));
+                }
+                Function::Native(f) => {
+                    check_arity(&name, &f.args, args.len())?;
+                    return (f.func)(args, scope, ctx);
+                }
+            };
+            let arg_scope = f.bind_args(&name, args)?;
+            let body_scope = Scope::new(Some(&arg_scope));
+            let (last, init) = match f.body.split_last() {
+                Some(body) => body,
+                None => return Ok(Value::Null),
+            };
+            for node in init {
+                node.evaluate(&body_scope, ctx)?;
+            }
+            match last.evaluate_tail(&body_scope, ctx)? {
+                Tail::Value(v) => return Ok(v),
+                Tail::Call(next_name, next_function, next_args) => {
+                    name = next_name;
+                    function = next_function;
+                    args = next_args;
                 }
-                Ok(result)
             }
-            Node::Variable(name) => Ok(scope.get(name).unwrap().clone()),
-            Node::EOF => Ok(Value::Null),
         }
     }
 }
 
+impl UserDefinedFunction {
+    /// Checks the arguments against the parameters and binds them in a new
+    /// scope under the function's own.
+    fn bind_args(&self, name: &str, args: Vec<Value>) -> Result<Scope, String> {
+        check_arity(name, &self.args, args.len())?;
+        let arg_scope = Scope::new(Some(&self.scope));
+        let mut values = args.into_iter();
+        let mut params = self.args.iter();
+        while let Some(param) = params.next() {
+            if param == "&" {
+                if let Some(rest) = params.next() {
+                    let rest_args = values.by_ref().map(Node::Atom).collect();
+                    arg_scope.set(rest.clone(), Value::List(rest_args));
+                }
+                break;
+            }
+            if let Some(value) = values.next() {
+                arg_scope.set(param.clone(), value);
+            }
+        }
+        Ok(arg_scope)
+    }
+}
+
+/// Calls a macro with its unevaluated argument forms and returns the form
+/// it expands to. Kept out of `evaluate_tail` so that the frames of
+/// ordinary calls stay small.
+#[inline(never)]
+fn expand_macro(
+    m: UserDefinedFunction,
+    name: &str,
+    args: &[Node],
+    scope: &Scope,
+    ctx: &mut Context,
+) -> Result<Node, String> {
+    let forms = args.iter().map(macro_argument).collect();
+    let expansion = Function::UserDefined(m).call(name, forms, scope, ctx)?;
+    Ok(expansion_to_node(expansion))
+}
+
+/// The value a macro receives for an argument form. Literals are passed as
+/// their value, so that the macro can compute on them, and `[..]` lists as
+/// lists. Names and calls are passed quoted, except inside a list, where
+/// they are kept as forms so that the list still reads back as the list it
+/// was written as.
+fn macro_argument(form: &Node) -> Value {
+    match form {
+        Node::Atom(Value::List(nodes)) => Value::List(
+            nodes
+                .iter()
+                .map(|node| match node {
+                    Node::Atom(_) => Node::Atom(macro_argument(node)),
+                    node => node.clone(),
+                })
+                .collect(),
+        ),
+        Node::Atom(v) => v.clone(),
+        form => Value::Quoted(Box::new(form.clone())),
+    }
+}
+
+/// Turns the value a macro returned into the form it stands for. Quoted
+/// values stand for the quoted form, symbols for the variable they name,
+/// and a list whose first element is a symbol stands for a call to it. Any
+/// other value stands for itself. A list that starts with a form rather
+/// than a value is a list argument passed through, and stays a list.
+fn expansion_to_node(value: Value) -> Node {
+    match value {
+        Value::Quoted(node) => *node,
+        Value::Symbol(name) => Node::Variable(name),
+        Value::List(nodes) => {
+            let call = matches!(nodes.first(), Some(Node::Atom(_)));
+            let mut forms: Vec<Node> = nodes
+                .into_iter()
+                .map(|node| match node {
+                    Node::Atom(v) => expansion_to_node(v),
+                    node => node,
+                })
+                .collect();
+            match forms.first() {
+                Some(Node::Variable(name)) if call => {
+                    let name = name.clone();
+                    forms.remove(0);
+                    Node::FunctionCall(name, forms)
+                }
+                _ => Node::Atom(Value::List(forms)),
+            }
+        }
+        v => Node::Atom(v),
+    }
+}
+
+/// Parameter lists may end in `&` followed by a name, meaning the function
+/// accepts any number of arguments beyond the ones before it. A user
+/// defined function receives those as a list bound to that name.
+fn check_arity(name: &str, params: &[String], given: usize) -> Result<(), String> {
+    match params.iter().position(|p| p == "&") {
+        Some(required) if given < required => Err(format!(
+            "Function {} takes at least {} arguments, but {} were given",
+            name, required, given
+        )),
+        Some(_) => Ok(()),
+        None if given != params.len() => Err(format!(
+            "Function {} takes {} arguments, but {} were given",
+            name,
+            params.len(),
+            given
+        )),
+        None => Ok(()),
+    }
+}
+
 impl Value {
-    pub fn evaluate(&self, scope: &Scope) -> Value {
-        match self {
-            Value::Number(n) => Value::Number(*n),
-            Value::String(s) => Value::String(s.clone()),
-            Value::Boolean(b) => Value::Boolean(*b),
-            Value::List(l) => Value::List(
-                l.iter()
-                    .map(|n| Node::Atom(n.evaluate(scope).unwrap()))
-                    .collect(),
-            ),
-            Value::Map(m) => Value::Map(
-                m.iter()
-                    .map(|(k, v)| (k.clone(), v.evaluate(scope)))
-                    .collect(),
-            ),
-            Value::Function(f) => Value::Function(f.clone()),
-            Value::Null => Value::Null,
+    /// The name of the value's type, as returned by `type` and used in
+    /// error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Number(_) => "float",
+            Value::String(_) => "string",
+            Value::Char(_) => "char",
+            Value::Boolean(_) => "bool",
+            Value::Keyword(_) => "keyword",
+            Value::Symbol(_) => "symbol",
+            Value::List(_) => "list",
+            Value::Pair(_, _) => "pair",
+            Value::Map(_) => "map",
+            Value::Function(_) => "function",
+            Value::Quoted(_) => "quoted",
+            Value::Null => "null",
         }
     }
+
+    /// The value of an integer or float. Unlike `cast_to_number`, nothing
+    /// else converts.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// The elements of a list. Evaluated lists hold `Node::Atom`s.
+    pub fn as_list(&self) -> Option<&[Node]> {
+        match self {
+            Value::List(nodes) => Some(nodes),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&HashMap<MapKey, Value>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// The elements of any collection, for builtins that treat them alike:
+    /// a list's elements, a string's chars, and a map's entries as `[key
+    /// value]` lists ordered by key. Null is the empty sequence.
+    pub fn as_seq(&self) -> Option<Vec<Value>> {
+        match self {
+            Value::List(nodes) => nodes
+                .iter()
+                .map(|node| match node {
+                    Node::Atom(v) => Some(v.clone()),
+                    _ => None,
+                })
+                .collect(),
+            Value::String(s) => Some(s.chars().map(Value::Char).collect()),
+            Value::Map(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by_key(|(k, _)| *k);
+                Some(
+                    entries
+                        .into_iter()
+                        .map(|(k, v)| Value::from(vec![k.to_value(), v.clone()]))
+                        .collect(),
+                )
+            }
+            Value::Null => Some(vec![]),
+            _ => None,
+        }
+    }
+
+    /// Numbers, strings, chars, booleans and keywords can key maps. NaN,
+    /// collections, functions and null are rejected.
+    pub fn to_map_key(&self) -> Result<MapKey, String> {
+        match self {
+            Value::Integer(i) => Ok(MapKey::Integer(*i)),
+            Value::Number(n) if integer_equals_float(*n as i64, *n) => {
+                Ok(MapKey::Integer(*n as i64))
+            }
+            Value::Number(n) if !n.is_nan() => Ok(MapKey::Number(n + 0.0)),
+            Value::String(s) => Ok(MapKey::String(s.clone())),
+            Value::Char(c) => Ok(MapKey::Char(*c)),
+            Value::Boolean(b) => Ok(MapKey::Boolean(*b)),
+            Value::Keyword(k) => Ok(MapKey::Keyword(k.clone())),
+            v => Err(format!("{} cannot be used as a map key", v)),
+        }
+    }
+
+    /// Evaluates the elements of list, pair and map literals; other values
+    /// evaluate to themselves.
+    pub fn evaluate(&self, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+        match self {
+            Value::List(l) => {
+                let mut values = vec![];
+                for node in l {
+                    values.push(Node::Atom(node.evaluate(scope, ctx)?));
+                }
+                Ok(Value::List(values))
+            }
+            Value::Pair(head, tail) => Ok(Value::Pair(
+                Box::new(Node::Atom(head.evaluate(scope, ctx)?)),
+                Box::new(Node::Atom(tail.evaluate(scope, ctx)?)),
+            )),
+            Value::Map(m) => {
+                let mut values = HashMap::new();
+                for (k, v) in m {
+                    values.insert(k.clone(), v.evaluate(scope, ctx)?);
+                }
+                Ok(Value::Map(values))
+            }
+            v => Ok(v.clone()),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Value {
+        Value::Integer(i)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Boolean(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(values: Vec<Value>) -> Value {
+        Value::List(values.into_iter().map(Node::Atom).collect())
+    }
+}
+
+/// The error of a failed `TryFrom<Value>` conversion.
+fn conversion_error(expected: &str, value: &Value) -> InterpretatorError {
+    InterpretatorError::CastError(format!(
+        "expected {}, got {} {}",
+        expected,
+        value.type_name(),
+        value
+    ))
+}
+
+/// Integers convert too, like `as_number`.
+impl TryFrom<Value> for f64 {
+    type Error = InterpretatorError;
+
+    fn try_from(value: Value) -> Result<f64, InterpretatorError> {
+        value
+            .as_number()
+            .ok_or_else(|| conversion_error("a number", &value))
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = InterpretatorError;
+
+    fn try_from(value: Value) -> Result<i64, InterpretatorError> {
+        value
+            .as_integer()
+            .ok_or_else(|| conversion_error("an integer", &value))
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = InterpretatorError;
+
+    fn try_from(value: Value) -> Result<bool, InterpretatorError> {
+        value
+            .as_bool()
+            .ok_or_else(|| conversion_error("a bool", &value))
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = InterpretatorError;
+
+    fn try_from(value: Value) -> Result<String, InterpretatorError> {
+        match value {
+            Value::String(s) => Ok(s),
+            v => Err(conversion_error("a string", &v)),
+        }
+    }
+}
+
+impl Value {
+    /// Compares lists, pairs and maps element by element, maps regardless
+    /// of insertion order. Unlike `==`, an integer never equals a float, so
+    /// `1` and `1.0` differ, also inside collections.
+    pub fn structurally_equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Integer(_), Value::Number(_)) | (Value::Number(_), Value::Integer(_)) => false,
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| nodes_structurally_equal(a, b))
+            }
+            (Value::Pair(a, b), Value::Pair(c, d)) => {
+                nodes_structurally_equal(a, c) && nodes_structurally_equal(b, d)
+            }
+            (Value::Map(a), Value::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter().all(|(key, value)| {
+                        b.get(key).is_some_and(|v| value.structurally_equals(v))
+                    })
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+fn nodes_structurally_equal(a: &Node, b: &Node) -> bool {
+    match (a, b) {
+        (Node::Atom(a), Node::Atom(b)) => a.structurally_equals(b),
+        (a, b) => a == b,
+    }
+}
+
+/// Values are only ordered against values of the same type, with integers
+/// and floats counting as one type: numbers numerically, strings, keywords
+/// and symbols lexicographically, chars by code point
+/// and booleans with false < true.
+/// Lists, maps, functions and null have no ordering beyond equal values
+/// comparing as `Equal`, and cross-type comparisons return `None`.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Integer(a), Value::Number(b)) => compare_integer_float(*a, *b),
+            (Value::Number(a), Value::Integer(b)) => {
+                compare_integer_float(*b, *a).map(Ordering::reverse)
+            }
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Char(a), Value::Char(b)) => a.partial_cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+            (Value::Keyword(a), Value::Keyword(b)) => a.partial_cmp(b),
+            (Value::Symbol(a), Value::Symbol(b)) => a.partial_cmp(b),
+            (a, b) if a == b => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `items` separated by single spaces.
+fn write_spaced<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// Renders values as source text. Map entries are written in key order,
+/// natives by their name and null as `nil`. Floats with an integer value
+/// keep a `.0` so they read back as floats.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Number(n) if n.is_finite() && n.fract() == 0.0 => write!(f, "{:.1}", n),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "\"{}\"", s),
+            Value::Char(' ') => write!(f, "\\space"),
+            Value::Char('\n') => write!(f, "\\newline"),
+            Value::Char('\t') => write!(f, "\\tab"),
+            Value::Char(c) => write!(f, "\\{}", c),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Keyword(k) => write!(f, ":{}", k),
+            Value::Symbol(s) => write!(f, "{}", s),
+            Value::List(nodes) => {
+                write!(f, "[")?;
+                write_spaced(f, nodes)?;
+                write!(f, "]")
+            }
+            Value::Pair(head, tail) => write!(f, "({} . {})", head, tail),
+            Value::Map(map) => {
+                let mut keys: Vec<&MapKey> = map.keys().collect();
+                keys.sort();
+                write!(f, "{{")?;
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{} {}", key, map[key])?;
+                }
+                write!(f, "}}")
+            }
+            Value::Function(Function::Native(native)) => write!(f, "{}", native.name),
+            Value::Function(Function::UserDefined(function)) => {
+                write!(f, "(fn [{}]", function.args.join(" "))?;
+                for node in function.body.iter() {
+                    write!(f, " {}", node)?;
+                }
+                write!(f, ")")
+            }
+            Value::Function(Function::Macro(function)) => {
+                write!(f, "(macro [{}]", function.args.join(" "))?;
+                for node in function.body.iter() {
+                    write!(f, " {}", node)?;
+                }
+                write!(f, ")")
+            }
+            Value::Quoted(node) => write!(f, "(quote {})", node),
+            Value::Null => write!(f, "nil"),
+        }
+    }
+}
+
+/// Renders a syntax tree back into source text that parses to the same
+/// tree. A program is written one top level form per line.
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Atom(v) => write!(f, "{}", v),
+            Node::FunctionCall(name, args) => {
+                write!(f, "({}", name)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Node::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{} {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+            Node::Program(nodes) => {
+                let forms: Vec<&Node> = nodes.iter().filter(|n| **n != Node::EOF).collect();
+                for (i, node) in forms.into_iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", node)?;
+                }
+                Ok(())
+            }
+            Node::Variable(name) => write!(f, "{}", name),
+            Node::Splice(node) => write!(f, "@{}", node),
+            Node::EOF => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parser::Parser;
+    use super::*;
+
+    fn parse(source: &str) -> Node {
+        Parser::from_source(source.to_owned())
+            .unwrap()
+            .parse_program()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_value_order_numbers() {
+        assert!(Value::Number(1.0) < Value::Number(2.0));
+        assert!(Value::Number(-3.5) < Value::Number(-1.0));
+        assert_eq!(
+            Value::Number(2.0).partial_cmp(&Value::Number(2.0)),
+            Some(Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn test_value_order_strings() {
+        assert!(Value::String("apple".to_string()) < Value::String("banana".to_string()));
+        assert!(Value::String("B".to_string()) < Value::String("a".to_string()));
+    }
+
+    #[test]
+    fn test_value_order_booleans() {
+        assert!(Value::Boolean(false) < Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_value_order_cross_type() {
+        assert_eq!(
+            Value::Number(1.0).partial_cmp(&Value::String("1".to_string())),
+            None
+        );
+        assert_eq!(Value::Boolean(true).partial_cmp(&Value::Number(1.0)), None);
+        assert_eq!(Value::Null.partial_cmp(&Value::Number(0.0)), None);
+    }
+
+    #[test]
+    fn test_value_order_lists() {
+        let a = Value::List(vec![Node::Atom(Value::Number(1.0))]);
+        let b = Value::List(vec![Node::Atom(Value::Number(2.0))]);
+        assert_eq!(a.partial_cmp(&b), None);
+        assert_eq!(a.partial_cmp(&a.clone()), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn test_display_atoms() {
+        assert_eq!(Value::Integer(1).to_string(), "1");
+        assert_eq!(Value::Number(1.0).to_string(), "1.0");
+        assert_eq!(Value::Number(-2.5).to_string(), "-2.5");
+        assert_eq!(Value::String("a b".to_string()).to_string(), "\"a b\"");
+        assert_eq!(Value::Boolean(true).to_string(), "true");
+        assert_eq!(Value::Null.to_string(), "nil");
+        assert_eq!(Value::Char('a').to_string(), "\\a");
+        assert_eq!(Value::Char(' ').to_string(), "\\space");
+        assert_eq!(Value::Keyword("a".to_string()).to_string(), ":a");
+    }
+
+    #[test]
+    fn test_display_function_call() {
+        let node = Node::FunctionCall(
+            "str".to_string(),
+            vec![
+                Node::Variable("a".to_string()),
+                Node::Atom(Value::List(vec![Node::Atom(Value::Integer(1))])),
+                Node::FunctionCall("f".to_string(), vec![]),
+            ],
+        );
+        assert_eq!(node.to_string(), "(str a [1] (f))");
+    }
+
+    #[test]
+    fn test_display_program() {
+        assert_eq!(
+            parse("(def a   1)\n\n  (defn f [x y]\n (+ x y))  {\"k\" [true]}").to_string(),
+            "(def a 1)\n(defn f [x y] (+ x y))\n{\"k\" [true]}"
+        );
+        assert_eq!(parse("").to_string(), "");
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let source =
+            "(defn f [a & rest] (if (< a 2) [a rest] {a \"x\" \"y\" [1.5 false]})) (f 1 2 3)";
+        let node = parse(source);
+        assert_eq!(parse(&node.to_string()), node);
+    }
+
+    #[test]
+    fn test_display_evaluated_values() {
+        let map: HashMap<MapKey, Value> = vec![
+            (MapKey::String("b".to_string()), Value::Integer(2)),
+            (MapKey::String("a".to_string()), Value::Null),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(Value::Map(map).to_string(), "{\"a\" nil \"b\" 2}");
+        let map: HashMap<MapKey, Value> = vec![
+            (MapKey::Keyword("k".to_string()), Value::Null),
+            (MapKey::Integer(2), Value::Null),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(Value::Map(map).to_string(), "{2 nil :k nil}");
+        let function = Value::Function(Function::UserDefined(UserDefinedFunction {
+            args: vec!["x".to_string()],
+            body: vec![Node::Variable("x".to_string())].into(),
+            scope: Scope::new(None),
+        }));
+        assert_eq!(function.to_string(), "(fn [x] x)");
+    }
+
+    #[test]
+    fn test_accessors() {
+        assert_eq!(Value::Number(1.5).as_number(), Some(1.5));
+        assert_eq!(Value::Integer(2).as_number(), Some(2.0));
+        assert_eq!(Value::String("2".to_string()).as_number(), None);
+        assert_eq!(Value::Integer(2).as_integer(), Some(2));
+        assert_eq!(Value::Number(2.0).as_integer(), None);
+        assert_eq!(Value::String("a".to_string()).as_string(), Some("a"));
+        assert_eq!(Value::Keyword("a".to_string()).as_string(), None);
+        assert_eq!(Value::Boolean(false).as_bool(), Some(false));
+        assert_eq!(Value::Null.as_bool(), None);
+    }
+
+    #[test]
+    fn test_collection_accessors() {
+        let list = Value::List(vec![Node::Atom(Value::Integer(1))]);
+        assert_eq!(list.as_list(), Some(&[Node::Atom(Value::Integer(1))][..]));
+        assert_eq!(list.as_map(), None);
+        let mut map = HashMap::new();
+        map.insert(MapKey::Keyword("k".to_string()), Value::Null);
+        let map = Value::Map(map);
+        assert_eq!(map.as_map().map(|m| m.len()), Some(1));
+        assert_eq!(map.as_list(), None);
+    }
+
+    #[test]
+    fn test_as_seq() {
+        let list = Value::List(vec![Node::Atom(Value::Integer(1))]);
+        assert_eq!(list.as_seq(), Some(vec![Value::Integer(1)]));
+        assert_eq!(
+            Value::from("ab").as_seq(),
+            Some(vec![Value::Char('a'), Value::Char('b')])
+        );
+        let mut map = HashMap::new();
+        map.insert(MapKey::Integer(2), Value::from("b"));
+        map.insert(MapKey::Integer(1), Value::from("a"));
+        assert_eq!(
+            Value::Map(map).as_seq(),
+            Some(vec![
+                Value::from(vec![Value::Integer(1), Value::from("a")]),
+                Value::from(vec![Value::Integer(2), Value::from("b")]),
+            ])
+        );
+        assert_eq!(Value::Null.as_seq(), Some(vec![]));
+        assert_eq!(Value::Integer(1).as_seq(), None);
+        let unevaluated = Value::List(vec![Node::Variable("x".to_string())]);
+        assert_eq!(unevaluated.as_seq(), None);
+    }
+
+    #[test]
+    fn test_from_rust_values() {
+        assert_eq!(Value::from(1.5), Value::Number(1.5));
+        assert!(matches!(Value::from(3i64), Value::Integer(3)));
+        assert_eq!(Value::from(true), Value::Boolean(true));
+        assert_eq!(Value::from("a"), Value::String("a".to_string()));
+        assert_eq!(Value::from("b".to_string()), Value::String("b".to_string()));
+        assert_eq!(
+            Value::from(vec![Value::from(1i64), Value::from("x")]),
+            Value::List(vec![
+                Node::Atom(Value::Integer(1)),
+                Node::Atom(Value::String("x".to_string())),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        assert_eq!(f64::try_from(Value::from(2.5)), Ok(2.5));
+        assert_eq!(f64::try_from(Value::from(2i64)), Ok(2.0));
+        assert_eq!(i64::try_from(Value::from(7i64)), Ok(7));
+        assert_eq!(bool::try_from(Value::from(false)), Ok(false));
+        assert_eq!(
+            String::try_from(Value::from("round trip")),
+            Ok("round trip".to_string())
+        );
+        assert_eq!(
+            f64::try_from(Value::from("1")),
+            Err(InterpretatorError::CastError(
+                "expected a number, got string \"1\"".to_string()
+            ))
+        );
+        assert!(String::try_from(Value::Keyword("k".to_string())).is_err());
+        assert!(bool::try_from(Value::Null).is_err());
+        assert!(i64::try_from(Value::from(1.0)).is_err());
+    }
 }
@@ -5,12 +5,16 @@ use std::error;
 use std::fmt;
 pub struct Parser {
     tokens: Vec<Token>,
+    /// Source positions of `tokens`, empty when the parser was built from
+    /// bare tokens.
+    positions: Vec<Position>,
     pos: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParserError {
-    UnexpectedToken(Token, String),
+    /// The token, what was wrong with it and where it appeared, if known.
+    UnexpectedToken(Token, String, Option<Position>),
     UnexpectedEndOfFile,
     ParserStateError(String),
 }
@@ -20,7 +24,12 @@ impl error::Error for ParserError {}
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ParserError::UnexpectedToken(t, s) => write!(f, "Unexpected token: {:?} {}", t, s),
+            ParserError::UnexpectedToken(t, s, Some(position)) => {
+                write!(f, "Unexpected token at {}: {:?} {}", position, t, s)
+            }
+            ParserError::UnexpectedToken(t, s, None) => {
+                write!(f, "Unexpected token: {:?} {}", t, s)
+            }
             ParserError::UnexpectedEndOfFile => write!(f, "Unexpected end of file"),
             ParserError::ParserStateError(s) => write!(f, "Parser state error: {}", s),
         }
@@ -30,25 +39,49 @@ impl fmt::Display for ParserError {
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
         Parser {
-            tokens: tokens,
+            tokens,
+            positions: vec![],
             pos: 0,
         }
     }
 
     pub fn from_source(source: String) -> Result<Parser, LexerError> {
-        let tokens = Lexer::new(source).tokenize()?;
-        Ok(Parser::new(tokens))
+        let (tokens, positions) = Lexer::new(source)
+            .tokenize_with_positions()?
+            .into_iter()
+            .unzip();
+        Ok(Parser {
+            tokens,
+            positions,
+            pos: 0,
+        })
+    }
+
+    /// An `UnexpectedToken` error for the current token.
+    fn unexpected(&self, message: String) -> ParserError {
+        ParserError::UnexpectedToken(
+            self.curr_token().clone(),
+            message,
+            self.positions.get(self.pos).copied(),
+        )
     }
 
     fn curr_token(&self) -> &Token {
-        &self.tokens[self.pos]
+        self.peek_token(0)
+    }
+
+    /// Returns the token `n` positions after the current one without
+    /// consuming anything, or `Token::EOF` past the end of the input.
+    pub fn peek_token(&self, n: usize) -> &Token {
+        static EOF: Token = Token::EOF;
+        self.tokens.get(self.pos + n).unwrap_or(&EOF)
     }
 
     pub fn parse(&mut self) -> Result<Node, ParserError> {
-        return self.parseProgram();
+        self.parse_program()
     }
 
-    pub fn parseProgram(&mut self) -> Result<Node, ParserError> {
+    pub fn parse_program(&mut self) -> Result<Node, ParserError> {
         let mut nodes = vec![];
         while self.pos < self.tokens.len() {
             nodes.push(self.parse_node()?);
@@ -58,47 +91,116 @@ impl Parser {
                 Node::EOF => Ok(Node::Program(nodes)),
                 _ => Err(ParserError::ParserStateError("Expected EOF".to_string())),
             },
-            None => Err(ParserError::ParserStateError("Empty program".to_string())),
+            None => Ok(Node::Program(vec![Node::EOF])),
         }
     }
 
-    pub fn parse_list(&mut self) -> Result<Node, ParserError> {
+    /// Parses forms up to and including `closer`. Any other closing
+    /// delimiter on the way belongs to an opener that is not the one being
+    /// parsed, and is reported as unexpected.
+    fn parse_until(&mut self, closer: Token) -> Result<Vec<Node>, ParserError> {
+        match self.parse_dotted_until(closer)? {
+            (nodes, None) => Ok(nodes),
+            (_, Some(_)) => Err(ParserError::ParserStateError(
+                "Dotted tails are only allowed in lists".to_string(),
+            )),
+        }
+    }
+
+    /// Like `parse_until`, but also accepts a `.` followed by a single form
+    /// right before `closer`, which is returned as the tail.
+    fn parse_dotted_until(
+        &mut self,
+        closer: Token,
+    ) -> Result<(Vec<Node>, Option<Node>), ParserError> {
         let mut nodes = vec![];
-        while self.curr_token() != &Token::CloseBracket {
+        loop {
+            let token = self.curr_token();
+            if token == &closer {
+                self.pos += 1;
+                return Ok((nodes, None));
+            }
+            if token == &Token::Dot {
+                if nodes.is_empty() {
+                    return Err(self.unexpected("needs a form before it".to_string()));
+                }
+                self.pos += 1;
+                let tail = match self.curr_token() {
+                    token if is_closer(token) || token == &Token::Dot => {
+                        return Err(self.unexpected("expected a form after .".to_string()))
+                    }
+                    _ => self.parse_node()?,
+                };
+                if tail == Node::EOF {
+                    return Err(ParserError::UnexpectedEndOfFile);
+                }
+                if self.curr_token() != &closer {
+                    let message = format!("expected {} after the dotted tail", delimiter(&closer));
+                    return Err(self.unexpected(message));
+                }
+                self.pos += 1;
+                return Ok((nodes, Some(tail)));
+            }
+            if is_closer(token) {
+                let message = format!(
+                    "expected {} but found {}",
+                    delimiter(&closer),
+                    delimiter(token)
+                );
+                return Err(self.unexpected(message));
+            }
             let node = self.parse_node()?;
             if node == Node::EOF {
                 return Err(ParserError::UnexpectedEndOfFile);
             }
             nodes.push(node);
         }
-        self.pos += 1;
-        Ok(Node::Atom(Value::List(nodes)))
     }
 
+    pub fn parse_list(&mut self) -> Result<Node, ParserError> {
+        match self.parse_dotted_until(Token::CloseBracket)? {
+            (nodes, None) => Ok(Node::Atom(Value::List(nodes))),
+            (nodes, Some(tail)) => Ok(dotted(nodes, tail)),
+        }
+    }
+
+    pub fn parse_map(&mut self) -> Result<Node, ParserError> {
+        let nodes = self.parse_until(Token::CloseBrace)?;
+        if nodes.len() % 2 != 0 {
+            return Err(ParserError::ParserStateError(
+                "Map literal must contain an even number of forms".to_string(),
+            ));
+        }
+        let mut entries = vec![];
+        let mut nodes = nodes.into_iter();
+        while let (Some(key), Some(value)) = (nodes.next(), nodes.next()) {
+            entries.push((key, value));
+        }
+        Ok(Node::Map(entries))
+    }
+
+    /// Parses a call, or a dotted list such as `(1 . 2)`, the only lists
+    /// written in parentheses that need not start with a name.
     pub fn parse_function_call(&mut self) -> Result<Node, ParserError> {
-        let name_node = self.parse_node()?;
-
-        let name = match name_node {
-            Node::Variable(name) => name,
-            _ => {
-                return Err(ParserError::UnexpectedToken(
-                    self.curr_token().clone(),
-                    format!("{:?} is not a variable", self.curr_token()),
-                ))
+        let name = match self.peek_token(0) {
+            Token::Identifier(name) => name.clone(),
+            token => {
+                let error = self.unexpected(format!("{:?} is not a variable", token));
+                return match self.parse_dotted_until(Token::CloseParen)? {
+                    (nodes, Some(tail)) => Ok(dotted(nodes, tail)),
+                    (_, None) => Err(error),
+                };
             }
         };
+        self.pos += 1;
 
-        let mut args = vec![];
-        while self.curr_token() != &Token::CloseParen {
-            let node = self.parse_node()?;
-            if node == Node::EOF {
-                return Err(ParserError::UnexpectedEndOfFile);
+        match self.parse_dotted_until(Token::CloseParen)? {
+            (args, None) => Ok(Node::FunctionCall(name, args)),
+            (mut nodes, Some(tail)) => {
+                nodes.insert(0, Node::Variable(name));
+                Ok(dotted(nodes, tail))
             }
-            args.push(node);
         }
-        self.pos += 1;
-
-        Ok(Node::FunctionCall(name.clone(), args))
     }
 
     pub fn parse_node(&mut self) -> Result<Node, ParserError> {
@@ -107,6 +209,10 @@ impl Parser {
                 self.pos += 1;
                 Ok(Node::EOF)
             }
+            Token::Integer(i) => {
+                self.pos += 1;
+                Ok(Node::Atom(Value::Integer(*i)))
+            }
             Token::Number(n) => {
                 self.pos += 1;
                 Ok(Node::Atom(Value::Number(*n)))
@@ -119,6 +225,33 @@ impl Parser {
                 self.pos += 1;
                 Ok(Node::Atom(Value::Boolean(*b)))
             }
+            Token::Char(c) => {
+                self.pos += 1;
+                Ok(Node::Atom(Value::Char(*c)))
+            }
+            Token::Keyword(k) => {
+                let node = Node::Atom(Value::Keyword(k.clone()));
+                self.pos += 1;
+                Ok(node)
+            }
+            Token::Nil => {
+                self.pos += 1;
+                Ok(Node::Atom(Value::Null))
+            }
+            Token::Quote => {
+                self.pos += 1;
+                match self.parse_node()? {
+                    Node::EOF => Err(ParserError::UnexpectedEndOfFile),
+                    node => Ok(Node::FunctionCall("quote".to_string(), vec![node])),
+                }
+            }
+            Token::Splice => {
+                self.pos += 1;
+                match self.parse_node()? {
+                    Node::EOF => Err(ParserError::UnexpectedEndOfFile),
+                    node => Ok(Node::Splice(Box::new(node))),
+                }
+            }
             Token::Identifier(s) => {
                 self.pos += 1;
                 Ok(Node::Variable(s.to_string()))
@@ -131,11 +264,122 @@ impl Parser {
                 self.pos += 1;
                 self.parse_function_call()
             }
-            _ => {
-                unimplemented!();
+            Token::OpenBrace => {
+                self.pos += 1;
+                self.parse_map()
+            }
+            token @ (Token::CloseParen | Token::CloseBracket | Token::CloseBrace) => {
+                Err(self.unexpected(format!("unmatched {}", delimiter(token))))
             }
+            Token::Dot => Err(self.unexpected("outside of a list".to_string())),
         }
     }
+
+    /// Parses every top-level form and returns the errors found.
+    pub fn check(&mut self) -> Vec<ParserError> {
+        self.parse_recovering().1
+    }
+
+    /// Like `parse_program`, but does not stop at the first error. After an
+    /// error the rest of the failed form is skipped and parsing carries on
+    /// with the next one, so independent mistakes are all reported. Returns
+    /// the program made of the forms that parsed, and the errors.
+    pub fn parse_recovering(&mut self) -> (Node, Vec<ParserError>) {
+        let mut nodes = vec![];
+        let mut errors = vec![];
+        while self.pos < self.tokens.len() {
+            let start = self.pos;
+            match self.parse_node() {
+                Ok(Node::EOF) => break,
+                Ok(node) => nodes.push(node),
+                Err(e) => {
+                    errors.push(e);
+                    self.skip_form(start);
+                }
+            }
+        }
+        nodes.push(Node::EOF);
+        (Node::Program(nodes), errors)
+    }
+
+    /// Moves past the top-level form starting at `start`: up to the closer
+    /// that balances its opener, or past a single token for forms that are
+    /// not delimited. Quotes are skipped along with the form they quote.
+    /// An unbalanced form also ends before a token at the start of a later
+    /// line, where the next top-level form most likely begins.
+    fn skip_form(&mut self, start: usize) {
+        self.pos = start;
+        let mut depth = 0;
+        loop {
+            if self.pos > start && self.starts_top_level_line(start) {
+                return;
+            }
+            match self.curr_token() {
+                Token::EOF => return,
+                Token::Quote | Token::Splice => {}
+                Token::OpenParen | Token::OpenBracket | Token::OpenBrace => depth += 1,
+                token if is_closer(token) && depth > 1 => depth -= 1,
+                token if is_closer(token) || depth == 0 => {
+                    self.pos += 1;
+                    return;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Whether the current token is in the first column of a line after
+    /// the one the token at `start` is on.
+    fn starts_top_level_line(&self, start: usize) -> bool {
+        match (self.positions.get(start), self.positions.get(self.pos)) {
+            (Some(start), Some(current)) => current.line > start.line && current.column == 1,
+            _ => false,
+        }
+    }
+}
+
+/// Builds the pairs of `(a b . tail)`, which is `(a . (b . tail))`.
+fn dotted(nodes: Vec<Node>, tail: Node) -> Node {
+    nodes.into_iter().rev().fold(tail, |tail, node| {
+        Node::Atom(Value::Pair(Box::new(node), Box::new(tail)))
+    })
+}
+
+fn is_closer(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::CloseParen | Token::CloseBracket | Token::CloseBrace
+    )
+}
+
+fn delimiter(token: &Token) -> &'static str {
+    match token {
+        Token::OpenParen => "(",
+        Token::CloseParen => ")",
+        Token::OpenBracket => "[",
+        Token::CloseBracket => "]",
+        Token::OpenBrace => "{",
+        Token::CloseBrace => "}",
+        _ => "a delimiter",
+    }
+}
+
+/// Parses one top level form at a time, stopping at the end of input or
+/// after the first error.
+impl Iterator for Parser {
+    type Item = Result<Node, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if *self.peek_token(0) == Token::EOF {
+            return None;
+        }
+        let form = self.parse_node();
+        if form.is_err() {
+            self.pos = self.tokens.len();
+        }
+        Some(form)
+    }
 }
 
 #[cfg(test)]
@@ -152,10 +396,10 @@ mod tests {
 
     #[test]
     fn test_parse_number() {
-        let tokens = vec![Token::Number(5.0), Token::EOF];
+        let tokens = vec![Token::Integer(5), Token::EOF];
         let mut parser = Parser::new(tokens);
         let node = parser.parse_node().unwrap();
-        assert_eq!(node, Node::Atom(Value::Number(5.0)));
+        assert_eq!(node, Node::Atom(Value::Integer(5)));
     }
 
     #[test]
@@ -177,9 +421,9 @@ mod tests {
     #[test]
     fn test_parse_program() {
         let tokens = vec![
-            Token::Number(1.0),
-            Token::Number(2.0),
-            Token::Number(3.0),
+            Token::Integer(1),
+            Token::Integer(2),
+            Token::Integer(3),
             Token::EOF,
         ];
         let mut parser = Parser::new(tokens);
@@ -187,9 +431,9 @@ mod tests {
         assert_eq!(
             program,
             Node::Program(vec![
-                Node::Atom(Value::Number(1.0)),
-                Node::Atom(Value::Number(2.0)),
-                Node::Atom(Value::Number(3.0)),
+                Node::Atom(Value::Integer(1)),
+                Node::Atom(Value::Integer(2)),
+                Node::Atom(Value::Integer(3)),
                 Node::EOF,
             ])
         );
@@ -199,9 +443,9 @@ mod tests {
     fn test_parse_list() {
         let tokens = vec![
             Token::OpenBracket,
-            Token::Number(1.0),
-            Token::Number(2.0),
-            Token::Number(3.0),
+            Token::Integer(1),
+            Token::Integer(2),
+            Token::Integer(3),
             Token::CloseBracket,
             Token::EOF,
         ];
@@ -210,9 +454,9 @@ mod tests {
         assert_eq!(
             list,
             Node::Atom(Value::List(vec![
-                Node::Atom(Value::Number(1.0)),
-                Node::Atom(Value::Number(2.0)),
-                Node::Atom(Value::Number(3.0)),
+                Node::Atom(Value::Integer(1)),
+                Node::Atom(Value::Integer(2)),
+                Node::Atom(Value::Integer(3)),
             ]))
         );
     }
@@ -223,9 +467,9 @@ mod tests {
             Token::OpenBracket,
             Token::String("foo".to_string()),
             Token::OpenBracket,
-            Token::Number(1.0),
-            Token::Number(2.0),
-            Token::Number(3.0),
+            Token::Integer(1),
+            Token::Integer(2),
+            Token::Integer(3),
             Token::CloseBracket,
             Token::CloseBracket,
             Token::EOF,
@@ -237,9 +481,9 @@ mod tests {
             Node::Atom(Value::List(vec![
                 Node::Atom(Value::String("foo".to_string())),
                 Node::Atom(Value::List(vec![
-                    Node::Atom(Value::Number(1.0)),
-                    Node::Atom(Value::Number(2.0)),
-                    Node::Atom(Value::Number(3.0)),
+                    Node::Atom(Value::Integer(1)),
+                    Node::Atom(Value::Integer(2)),
+                    Node::Atom(Value::Integer(3)),
                 ])),
             ]))
         );
@@ -250,22 +494,78 @@ mod tests {
     fn test_parse_not_closed_list() {
         let tokens = vec![
             Token::OpenBracket,
-            Token::Number(1.0),
-            Token::Number(2.0),
-            Token::Number(3.0),
+            Token::Integer(1),
+            Token::Integer(2),
+            Token::Integer(3),
             Token::EOF,
         ];
         let mut parser = Parser::new(tokens);
         let _list = parser.parse_node().unwrap();
     }
 
+    #[test]
+    fn test_parse_map() {
+        let tokens = vec![
+            Token::OpenBrace,
+            Token::String("a".to_string()),
+            Token::Integer(1),
+            Token::String("b".to_string()),
+            Token::OpenBracket,
+            Token::CloseBracket,
+            Token::CloseBrace,
+            Token::EOF,
+        ];
+        let mut parser = Parser::new(tokens);
+        let map = parser.parse_node().unwrap();
+        assert_eq!(
+            map,
+            Node::Map(vec![
+                (
+                    Node::Atom(Value::String("a".to_string())),
+                    Node::Atom(Value::Integer(1))
+                ),
+                (
+                    Node::Atom(Value::String("b".to_string())),
+                    Node::Atom(Value::List(vec![]))
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_map_odd_forms() {
+        let tokens = vec![
+            Token::OpenBrace,
+            Token::String("a".to_string()),
+            Token::CloseBrace,
+            Token::EOF,
+        ];
+        let mut parser = Parser::new(tokens);
+        assert!(matches!(
+            parser.parse_node(),
+            Err(ParserError::ParserStateError(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_not_closed_map() {
+        let tokens = vec![
+            Token::OpenBrace,
+            Token::String("a".to_string()),
+            Token::Integer(1),
+            Token::EOF,
+        ];
+        let mut parser = Parser::new(tokens);
+        assert_eq!(parser.parse_node(), Err(ParserError::UnexpectedEndOfFile));
+    }
+
     #[test]
     fn test_parse_function_call() {
         let tokens = vec![
             Token::OpenParen,
             Token::Identifier("foo".to_string()),
-            Token::Number(1.0),
-            Token::Number(2.0),
+            Token::Integer(1),
+            Token::Integer(2),
             Token::CloseParen,
             Token::EOF,
         ];
@@ -275,10 +575,7 @@ mod tests {
             function_call,
             Node::FunctionCall(
                 "foo".to_string(),
-                vec![
-                    Node::Atom(Value::Number(1.0)),
-                    Node::Atom(Value::Number(2.0))
-                ]
+                vec![Node::Atom(Value::Integer(1)), Node::Atom(Value::Integer(2))]
             )
         );
     }
@@ -289,8 +586,8 @@ mod tests {
         let tokens = vec![
             Token::OpenParen,
             Token::Identifier("foo".to_string()),
-            Token::Number(1.0),
-            Token::Number(2.0),
+            Token::Integer(1),
+            Token::Integer(2),
             Token::EOF,
         ];
         let mut parser = Parser::new(tokens);
@@ -327,13 +624,308 @@ mod tests {
                 ),
                 Node::FunctionCall(
                     "foo".to_string(),
-                    vec![
-                        Node::Atom(Value::Number(1.0)),
-                        Node::Atom(Value::Number(2.0)),
-                    ]
+                    vec![Node::Atom(Value::Integer(1)), Node::Atom(Value::Integer(2)),]
                 ),
                 Node::EOF
             ])
         );
     }
+
+    fn parse_source(source: &str) -> Result<Node, ParserError> {
+        Parser::from_source(source.to_owned()).unwrap().parse()
+    }
+
+    #[test]
+    fn test_parse_mismatched_paren() {
+        assert_eq!(
+            parse_source("(a b]"),
+            Err(ParserError::UnexpectedToken(
+                Token::CloseBracket,
+                "expected ) but found ]".to_string(),
+                Some(Position { line: 1, column: 5 })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_mismatched_bracket() {
+        assert_eq!(
+            parse_source("[a b)"),
+            Err(ParserError::UnexpectedToken(
+                Token::CloseParen,
+                "expected ] but found )".to_string(),
+                Some(Position { line: 1, column: 5 })
+            ))
+        );
+        assert_eq!(
+            parse_source("{\"a\" [1 2}]"),
+            Err(ParserError::UnexpectedToken(
+                Token::CloseBrace,
+                "expected ] but found }".to_string(),
+                Some(Position {
+                    line: 1,
+                    column: 10
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_unmatched_closer() {
+        assert_eq!(
+            parse_source("(a) )"),
+            Err(ParserError::UnexpectedToken(
+                Token::CloseParen,
+                "unmatched )".to_string(),
+                Some(Position { line: 1, column: 5 })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_delimiters() {
+        assert_eq!(
+            parse_source("(f [1 {\"a\" (g [])}] 2)"),
+            Ok(Node::Program(vec![
+                Node::FunctionCall(
+                    "f".to_string(),
+                    vec![
+                        Node::Atom(Value::List(vec![
+                            Node::Atom(Value::Integer(1)),
+                            Node::Map(vec![(
+                                Node::Atom(Value::String("a".to_string())),
+                                Node::FunctionCall(
+                                    "g".to_string(),
+                                    vec![Node::Atom(Value::List(vec![]))]
+                                )
+                            )]),
+                        ])),
+                        Node::Atom(Value::Integer(2)),
+                    ]
+                ),
+                Node::EOF,
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_peek_token() {
+        let tokens = vec![
+            Token::OpenParen,
+            Token::Identifier("foo".to_string()),
+            Token::CloseParen,
+            Token::EOF,
+        ];
+        let mut parser = Parser::new(tokens);
+        assert_eq!(parser.peek_token(0), &Token::OpenParen);
+        assert_eq!(parser.peek_token(1), &Token::Identifier("foo".to_string()));
+        assert_eq!(parser.peek_token(3), &Token::EOF);
+        assert_eq!(parser.peek_token(10), &Token::EOF);
+        parser.parse_node().unwrap();
+        assert_eq!(parser.peek_token(0), &Token::EOF);
+        assert_eq!(parser.peek_token(1), &Token::EOF);
+    }
+
+    #[test]
+    fn test_parse_function_call_without_name() {
+        assert_eq!(
+            parse_source("(1 2)"),
+            Err(ParserError::UnexpectedToken(
+                Token::Integer(1),
+                "Integer(1) is not a variable".to_string(),
+                Some(Position { line: 1, column: 2 })
+            ))
+        );
+        assert!(parse_source("()").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let err = parse_source("(defn f [x]\n  (+ x 1]))").unwrap_err();
+        assert_eq!(
+            err,
+            ParserError::UnexpectedToken(
+                Token::CloseBracket,
+                "expected ) but found ]".to_string(),
+                Some(Position { line: 2, column: 9 })
+            )
+        );
+        assert_eq!(
+            err.to_string(),
+            "Unexpected token at 2:9: CloseBracket expected ) but found ]"
+        );
+    }
+
+    #[test]
+    fn test_parse_error_without_positions() {
+        let tokens = vec![Token::CloseParen, Token::EOF];
+        let err = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            err,
+            ParserError::UnexpectedToken(Token::CloseParen, "unmatched )".to_string(), None)
+        );
+        assert_eq!(err.to_string(), "Unexpected token: CloseParen unmatched )");
+    }
+
+    #[test]
+    fn test_parse_quote_shorthand() {
+        assert_eq!(parse_source("'a"), parse_source("(quote a)"));
+        assert_eq!(
+            parse_source("'(f [1] 'b)"),
+            parse_source("(quote (f [1] (quote b)))")
+        );
+        assert_eq!(parse_source("'"), Err(ParserError::UnexpectedEndOfFile));
+    }
+
+    #[test]
+    fn test_parse_splice() {
+        assert_eq!(
+            parse_source("(+ 1 @xs @(range 2))"),
+            Ok(Node::Program(vec![
+                Node::FunctionCall(
+                    "+".to_string(),
+                    vec![
+                        Node::Atom(Value::Integer(1)),
+                        Node::Splice(Box::new(Node::Variable("xs".to_string()))),
+                        Node::Splice(Box::new(Node::FunctionCall(
+                            "range".to_string(),
+                            vec![Node::Atom(Value::Integer(2))]
+                        ))),
+                    ]
+                ),
+                Node::EOF,
+            ]))
+        );
+        assert!(parse_source("(f @)").is_err());
+        assert_eq!(parse_source("@"), Err(ParserError::UnexpectedEndOfFile));
+    }
+
+    #[test]
+    fn test_parse_nil() {
+        assert_eq!(
+            parse_source("nil"),
+            Ok(Node::Program(vec![Node::Atom(Value::Null), Node::EOF]))
+        );
+    }
+
+    #[test]
+    fn test_parse_char() {
+        assert_eq!(
+            parse_source("[\\a \\space]"),
+            Ok(Node::Program(vec![
+                Node::Atom(Value::List(vec![
+                    Node::Atom(Value::Char('a')),
+                    Node::Atom(Value::Char(' ')),
+                ])),
+                Node::EOF,
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_keyword() {
+        assert_eq!(
+            parse_source("(get m :a)"),
+            Ok(Node::Program(vec![
+                Node::FunctionCall(
+                    "get".to_string(),
+                    vec![
+                        Node::Variable("m".to_string()),
+                        Node::Atom(Value::Keyword("a".to_string())),
+                    ]
+                ),
+                Node::EOF,
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_dotted_pair() {
+        let pair = |head, tail| Node::Atom(Value::Pair(Box::new(head), Box::new(tail)));
+        assert_eq!(
+            parse_source("(1 . 2)"),
+            Ok(Node::Program(vec![
+                pair(Node::Atom(Value::Integer(1)), Node::Atom(Value::Integer(2))),
+                Node::EOF,
+            ]))
+        );
+        assert_eq!(
+            parse_source("[a b . c]"),
+            Ok(Node::Program(vec![
+                pair(
+                    Node::Variable("a".to_string()),
+                    pair(
+                        Node::Variable("b".to_string()),
+                        Node::Variable("c".to_string())
+                    )
+                ),
+                Node::EOF,
+            ]))
+        );
+        assert_eq!(parse_source("(1 . (2 . 3))"), parse_source("[1 2 . 3]"));
+        assert_eq!(parse_source("'(1 . 2)"), parse_source("(quote (1 . 2))"));
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let mut parser =
+            Parser::from_source("(def a 1)\n(f ]\n[1 2]\n{1}\n(g a)".to_string()).unwrap();
+        let (program, errors) = parser.parse_recovering();
+        assert_eq!(program, parse_source("(def a 1)\n[1 2]\n(g a)").unwrap());
+        assert_eq!(
+            errors,
+            vec![
+                ParserError::UnexpectedToken(
+                    Token::CloseBracket,
+                    "expected ) but found ]".to_string(),
+                    Some(Position { line: 2, column: 4 })
+                ),
+                ParserError::ParserStateError(
+                    "Map literal must contain an even number of forms".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_recovering_at_line_start() {
+        let mut parser =
+            Parser::from_source("(def a (f 1\n  2)\n(def b 2)\n[3".to_string()).unwrap();
+        let (program, errors) = parser.parse_recovering();
+        assert_eq!(program, parse_source("(def b 2)").unwrap());
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1], ParserError::UnexpectedEndOfFile);
+        let (program, errors) = Parser::from_source("1 2".to_string())
+            .unwrap()
+            .parse_recovering();
+        assert_eq!(program, parse_source("1 2").unwrap());
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn test_parse_invalid_dotted_pair() {
+        assert!(parse_source("(. 2)").is_err());
+        assert!(parse_source("(1 .)").is_err());
+        assert!(parse_source("(1 . 2 3)").is_err());
+        assert!(parse_source("[1 . 2 . 3]").is_err());
+        assert!(parse_source("{1 . 2}").is_err());
+        assert_eq!(parse_source("(1 . "), Err(ParserError::UnexpectedEndOfFile));
+    }
+
+    #[test]
+    fn test_iterate_forms() {
+        let parser = Parser::from_source("(def a 1) [a 2] {\"k\" a}\n(f)".to_string()).unwrap();
+        let forms: Vec<Node> = parser.map(|form| form.unwrap()).collect();
+        assert_eq!(forms.len(), 4);
+        assert_eq!(forms[3], Node::FunctionCall("f".to_string(), vec![]));
+        assert_eq!(Parser::from_source(String::new()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_iterate_forms_stops_at_error() {
+        let mut parser = Parser::from_source("1 ) 2".to_string()).unwrap();
+        assert_eq!(parser.next(), Some(Ok(Node::Atom(Value::Integer(1)))));
+        assert!(matches!(parser.next(), Some(Err(_))));
+        assert_eq!(parser.next(), None);
+    }
 }
@@ -0,0 +1,115 @@
+use super::super::interpretator::{Context, Scope};
+use super::super::nodes::*;
+use super::define;
+use std::convert::TryFrom;
+
+pub fn register(scope: &Scope) {
+    define(scope, "bit-and", &["a", "b"], bit_and);
+    define(scope, "bit-or", &["a", "b"], bit_or);
+    define(scope, "bit-xor", &["a", "b"], bit_xor);
+    define(scope, "bit-not", &["a"], bit_not);
+    define(scope, "bit-shl", &["a", "n"], bit_shl);
+    define(scope, "bit-shr", &["a", "n"], bit_shr);
+}
+
+/// The arguments of the native `name` as integers. Floats are accepted
+/// only when they are whole.
+fn integers(name: &str, args: &[Value]) -> Result<Vec<i64>, String> {
+    args.iter()
+        .map(|arg| match arg {
+            Value::Integer(i) => Ok(*i),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() <= i64::MAX as f64 => Ok(*n as i64),
+            v => Err(format!(
+                "{} expects integers, got {} {}",
+                name,
+                v.type_name(),
+                v
+            )),
+        })
+        .collect()
+}
+
+fn bit_and(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let i = integers("bit-and", &args)?;
+    Ok(Value::Integer(i[0] & i[1]))
+}
+
+fn bit_or(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let i = integers("bit-or", &args)?;
+    Ok(Value::Integer(i[0] | i[1]))
+}
+
+fn bit_xor(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let i = integers("bit-xor", &args)?;
+    Ok(Value::Integer(i[0] ^ i[1]))
+}
+
+fn bit_not(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Integer(!integers("bit-not", &args)?[0]))
+}
+
+/// Shifts `a` by `n` bits, where `n` must be in `0..64`.
+fn shift(name: &str, args: &[Value], shift: fn(i64, u32) -> Option<i64>) -> Result<Value, String> {
+    let i = integers(name, args)?;
+    u32::try_from(i[1])
+        .ok()
+        .and_then(|n| shift(i[0], n))
+        .map(Value::Integer)
+        .ok_or_else(|| format!("{} shift amount {} is out of range 0..64", name, i[1]))
+}
+
+fn bit_shl(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    shift("bit-shl", &args, i64::checked_shl)
+}
+
+/// Shifts right arithmetically, so negative numbers stay negative.
+fn bit_shr(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    shift("bit-shr", &args, i64::checked_shr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    #[test]
+    fn test_bit_and_or_xor() {
+        assert_eq!(run("(bit-and 12 10)"), Ok(Value::Integer(8)));
+        assert_eq!(run("(bit-or 12 10)"), Ok(Value::Integer(14)));
+        assert_eq!(run("(bit-xor 12 10)"), Ok(Value::Integer(6)));
+        assert_eq!(run("(bit-and -1 255)"), Ok(Value::Integer(255)));
+        assert!(matches!(run("(bit-or 4.0 1)"), Ok(Value::Integer(5))));
+    }
+
+    #[test]
+    fn test_bit_not() {
+        assert_eq!(run("(bit-not 0)"), Ok(Value::Integer(-1)));
+        assert_eq!(run("(bit-not 5)"), Ok(Value::Integer(-6)));
+    }
+
+    #[test]
+    fn test_bit_shifts() {
+        assert_eq!(run("(bit-shl 1 10)"), Ok(Value::Integer(1024)));
+        assert_eq!(run("(bit-shr 1024 3)"), Ok(Value::Integer(128)));
+        assert_eq!(run("(bit-shr -16 2)"), Ok(Value::Integer(-4)));
+        assert_eq!(
+            run("(bit-shl 1 63)"),
+            Ok(Value::Integer(-9223372036854775808))
+        );
+        assert_eq!(
+            run("(bit-shl 1 64)"),
+            Err("EvaluationError: bit-shl shift amount 64 is out of range 0..64".to_string())
+        );
+        assert!(run("(bit-shr 1 -1)").is_err());
+    }
+
+    #[test]
+    fn test_bit_fractional_input() {
+        assert_eq!(
+            run("(bit-and 1.5 1)"),
+            Err("EvaluationError: bit-and expects integers, got float 1.5".to_string())
+        );
+        assert!(run("(bit-not \"1\")").is_err());
+        assert!(run("(bit-shl 1 0.5)").is_err());
+    }
+}
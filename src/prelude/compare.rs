@@ -0,0 +1,161 @@
+use super::super::interpretator::{Context, Scope};
+use super::super::nodes::*;
+use super::define;
+use std::cmp::Ordering;
+
+pub fn register(scope: &Scope) {
+    define(scope, "=", &["x", "&", "values"], equal);
+    define(scope, "equal?", &["a", "b"], structurally_equal);
+    define(scope, "<", &["x", "&", "values"], less);
+    define(scope, ">", &["x", "&", "values"], greater);
+    define(scope, "<=", &["x", "&", "values"], less_or_equal);
+    define(scope, ">=", &["x", "&", "values"], greater_or_equal);
+}
+
+/// Checks that every adjacent pair of `args` is ordered as `accept` allows.
+fn chain(name: &str, args: &[Value], accept: fn(Ordering) -> bool) -> Result<Value, String> {
+    for pair in args.windows(2) {
+        match pair[0].partial_cmp(&pair[1]) {
+            Some(ordering) if accept(ordering) => {}
+            Some(_) => return Ok(Value::Boolean(false)),
+            None => {
+                return Err(format!(
+                    "{} cannot compare {} {} and {} {}",
+                    name,
+                    pair[0].type_name(),
+                    pair[0],
+                    pair[1].type_name(),
+                    pair[1]
+                ))
+            }
+        }
+    }
+    Ok(Value::Boolean(true))
+}
+
+/// `(= x values...)` is true when all values are equal. Integers and
+/// floats compare by value, so `(= 3 3.0)` is true; other values are
+/// never equal to a value of a different type.
+fn equal(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(
+        args.windows(2).all(|pair| pair[0] == pair[1]),
+    ))
+}
+
+/// `(equal? a b)` compares lists and maps element by element, maps
+/// regardless of insertion order. Unlike `=` it tells integers and floats
+/// apart, so `(equal? 1 1.0)` is false. Functions are only equal to
+/// themselves, so two lambdas with the same text are not.
+fn structurally_equal(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(args[0].structurally_equals(&args[1])))
+}
+
+fn less(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    chain("<", &args, |o| o == Ordering::Less)
+}
+
+fn greater(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    chain(">", &args, |o| o == Ordering::Greater)
+}
+
+fn less_or_equal(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    chain("<=", &args, |o| o != Ordering::Greater)
+}
+
+fn greater_or_equal(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    chain(">=", &args, |o| o != Ordering::Less)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    #[test]
+    fn test_equal() {
+        assert_eq!(run("(= 1 1 1)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(= 1 1 2)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(= \"a\" \"a\")"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(= 1 \"1\")"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(= 1)"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_structurally_equal_lists() {
+        assert_eq!(
+            run("(equal? [1 [2 3]] [1 [2 3]])"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            run("(equal? [1 [2 3]] [1 [2 4]])"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(run("(equal? [1 2] [1 2 3])"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(equal? [] [])"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_structurally_equal_maps() {
+        assert_eq!(
+            run("(equal? {\"a\" 1 \"b\" [2]} {\"b\" [2] \"a\" 1})"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            run("(equal? {\"a\" 1} {\"a\" 1 \"b\" 2})"),
+            Ok(Value::Boolean(false))
+        );
+    }
+
+    #[test]
+    fn test_structurally_equal_numbers() {
+        assert_eq!(run("(equal? 1 1)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(equal? 1 1.0)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(equal? [1 [2]] [1 [2.0]])"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(equal? {:a 1} {:a 1.0})"), Ok(Value::Boolean(false)));
+        assert_eq!(
+            run("(equal? (cons 1 2) (cons 1 2.0))"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(run("(= [1] [1.0])"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_structurally_equal_functions() {
+        assert_eq!(run("(equal? + *)"), Ok(Value::Boolean(false)));
+        assert_eq!(
+            run("(equal? (fn [x] x) (fn [y] y))"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(
+            run("(equal? (fn [x] x) (fn [x] x))"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(
+            run("(defn make [] (fn [x] x)) (equal? (make) (make))"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(
+            run("(def f (fn [x] x)) (def g f) [(equal? f f) (equal? f g) (= f g)]"),
+            run("[true true true]")
+        );
+        assert_eq!(run("(equal? + +)"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert_eq!(run("(< 1 2 3)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(< 1 3 2)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(> 3 2 1)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(<= 1 1 2)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(>= 2 2 3)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(< \"a\" \"b\")"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_ordering_mixed_types() {
+        assert_eq!(
+            run("(< 1 \"2\")"),
+            Err("EvaluationError: < cannot compare integer 1 and string \"2\"".to_string())
+        );
+    }
+}
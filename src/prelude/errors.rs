@@ -0,0 +1,200 @@
+use super::super::interpretator::{Cast, Context, Scope};
+use super::super::nodes::*;
+use super::super::special_forms::is_truthy;
+use super::define;
+use std::collections::HashMap;
+
+pub fn register(scope: &Scope) {
+    define(scope, "error", &["message"], error);
+    define(scope, "assert", &["condition"], assert);
+    define(scope, "assert-eq", &["expected", "actual"], assert_eq);
+    define(scope, "ok", &["value"], ok);
+    define(scope, "err", &["error"], err);
+    define(scope, "ok?", &["value"], is_ok);
+    define(scope, "err?", &["value"], is_err);
+    define(scope, "unwrap", &["result"], unwrap);
+}
+
+/// `(error message)` fails with `message` as the error, which `try` can
+/// catch. Values that do not cast to a string are written as source.
+fn error(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Err(match args[0].cast_to_string() {
+        Ok(message) => message,
+        Err(_) => args[0].to_string(),
+    })
+}
+
+/// `(assert condition)` fails unless `condition` is truthy.
+fn assert(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    if is_truthy(&args[0])? {
+        Ok(Value::Null)
+    } else {
+        Err(format!("assertion failed: {} is not truthy", args[0]))
+    }
+}
+
+/// `(assert-eq expected actual)` fails unless the values are `equal?`.
+fn assert_eq(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    if args[0] == args[1] {
+        Ok(Value::Null)
+    } else {
+        Err(format!(
+            "assertion failed: expected {}, got {}",
+            args[0], args[1]
+        ))
+    }
+}
+
+/// Results are maps with a single `:ok` or `:err` key, so they print as
+/// `{:ok 1}` and work with `get`.
+fn tagged(tag: &str, value: Value) -> Value {
+    let mut map = HashMap::new();
+    map.insert(MapKey::Keyword(tag.to_string()), value);
+    Value::Map(map)
+}
+
+/// The tag and value of a result made by `ok` or `err`.
+fn untag(value: &Value) -> Option<(&str, &Value)> {
+    match value {
+        Value::Map(map) if map.len() == 1 => match map.iter().next() {
+            Some((MapKey::Keyword(tag), v)) if tag == "ok" || tag == "err" => Some((tag, v)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `(ok value)` wraps a successful result.
+fn ok(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(tagged("ok", args[0].clone()))
+}
+
+/// `(err error)` wraps a failed result.
+fn err(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(tagged("err", args[0].clone()))
+}
+
+fn is_ok(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(untag(&args[0]), Some(("ok", _)))))
+}
+
+fn is_err(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(untag(&args[0]), Some(("err", _)))))
+}
+
+/// `(unwrap result)` returns the value of an `ok`, and fails with the
+/// error of an `err`.
+fn unwrap(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    match untag(&args[0]) {
+        Some(("ok", v)) => Ok(v.clone()),
+        Some((_, e)) => Err(format!("unwrap called on an err: {}", e)),
+        None => Err(format!(
+            "unwrap expects an ok or err result, got {} {}",
+            args[0].type_name(),
+            args[0]
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    #[test]
+    fn test_error_uncaught() {
+        assert_eq!(
+            run("(error \"bad input\") 1"),
+            Err("EvaluationError: bad input".to_string())
+        );
+        assert_eq!(run("(error 42)"), Err("EvaluationError: 42".to_string()));
+        assert_eq!(
+            run("(error [1 \"a\"])"),
+            Err("EvaluationError: [1 \"a\"]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_error_caught() {
+        assert_eq!(
+            run("(try (error \"bad input\") (catch e e))"),
+            Ok(Value::String("bad input".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_error_from_nested_call() {
+        assert_eq!(
+            run(
+                "(defn check [n] (if (< n 0) (error (str \"negative: \" n)) n))
+                 (try (+ 1 (check -5)) (catch e e))"
+            ),
+            Ok(Value::String("negative: -5".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_assert() {
+        assert_eq!(run("(assert (= 1 1))"), Ok(Value::Null));
+        assert_eq!(
+            run("(assert [1])"),
+            Err("EvaluationError: CastError: Cannot cast list to bool".to_string())
+        );
+        assert_eq!(
+            run("(assert (< 2 1))"),
+            Err("EvaluationError: assertion failed: false is not truthy".to_string())
+        );
+        assert!(run("(assert nil)").is_err());
+    }
+
+    #[test]
+    fn test_assert_eq() {
+        assert_eq!(run("(assert-eq [1 [2]] [1 [2]])"), Ok(Value::Null));
+        assert_eq!(
+            run("(assert-eq [1 2] (list 1 3))"),
+            Err("EvaluationError: assertion failed: expected [1 2], got [1 3]".to_string())
+        );
+        assert_eq!(
+            run("(try (assert-eq \"a\" 1) (catch e e))"),
+            Ok(Value::String(
+                "assertion failed: expected \"a\", got 1".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_ok_and_err() {
+        assert_eq!(run("(ok 1)"), run("{:ok 1}"));
+        assert_eq!(run("(err \"boom\")"), run("{:err \"boom\"}"));
+        assert_eq!(
+            run("(str (ok [1]))"),
+            Ok(Value::String("{:ok [1]}".to_string()))
+        );
+        assert_eq!(run("(get (err 2) :err)"), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_ok_err_predicates() {
+        assert_eq!(run("[(ok? (ok 1)) (err? (ok 1))]"), run("[true false]"));
+        assert_eq!(run("[(ok? (err 1)) (err? (err 1))]"), run("[false true]"));
+        assert_eq!(
+            run("[(ok? 1) (err? {:err 1 :x 2}) (ok? {})]"),
+            run("[false false false]")
+        );
+    }
+
+    #[test]
+    fn test_unwrap() {
+        assert_eq!(run("(unwrap (ok 5))"), Ok(Value::Integer(5)));
+        assert_eq!(run("(unwrap (ok nil))"), Ok(Value::Null));
+        assert_eq!(
+            run("(unwrap (err \"not found\"))"),
+            Err("EvaluationError: unwrap called on an err: \"not found\"".to_string())
+        );
+        assert_eq!(
+            run("(try (unwrap (err 1)) (catch e :caught))"),
+            Ok(Value::Keyword("caught".to_string()))
+        );
+        assert!(run("(unwrap 5)").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use super::super::interpretator::{Context, Scope};
+use super::super::nodes::*;
+use super::{define, function_arg, list_arg, native};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub fn register(scope: &Scope) {
+    define(scope, "apply", &["f", "&", "args"], apply);
+    define(scope, "identity", &["x"], identity);
+    define(scope, "constantly", &["value"], constantly);
+    define(scope, "comp", &["f", "g"], comp);
+    define(scope, "partial", &["f", "&", "args"], partial);
+    define(scope, "memoize", &["f"], memoize);
+}
+
+/// `(apply f args... list)` calls `f` with `args` followed by the elements
+/// of `list`.
+fn apply(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let (list, spread) = match args[1..].split_last() {
+        Some(split) => split,
+        None => return Err("apply expects an argument list".to_string()),
+    };
+    let f = function_arg("apply", &args[0])?;
+    let mut call_args = spread.to_vec();
+    call_args.extend(list_arg("apply", list)?);
+    f.call("apply", call_args, scope, ctx)
+}
+
+fn identity(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(args[0].clone())
+}
+
+/// `(constantly value)` returns a function that ignores its arguments and
+/// returns `value`.
+fn constantly(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let value = args[0].clone();
+    Ok(native("constantly", &["&", "args"], move |_, _, _| {
+        Ok(value.clone())
+    }))
+}
+
+/// `(comp f g)` returns a function that calls `g` with its arguments and
+/// `f` with the result.
+fn comp(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let f = function_arg("comp", &args[0])?.clone();
+    let g = function_arg("comp", &args[1])?.clone();
+    Ok(native("comp", &["&", "args"], move |args, scope, ctx| {
+        let x = g.call("comp", args, scope, ctx)?;
+        f.call("comp", vec![x], scope, ctx)
+    }))
+}
+
+/// `(partial f args...)` returns a function that calls `f` with `args`
+/// followed by its own arguments.
+fn partial(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let f = function_arg("partial", &args[0])?.clone();
+    let bound = args[1..].to_vec();
+    Ok(native(
+        "partial",
+        &["&", "args"],
+        move |args, scope, ctx| {
+            let mut call_args = bound.clone();
+            call_args.extend(args);
+            f.call("partial", call_args, scope, ctx)
+        },
+    ))
+}
+
+/// The arguments of a memoized call, with their types.
+type CacheKey = Vec<(&'static str, MapKey)>;
+
+/// `(memoize f)` returns a function that calls `f` once for each distinct
+/// list of arguments and returns the remembered result after that.
+/// Arguments that cannot be map keys, such as lists, are not cached. The
+/// cache tells integers and floats apart, so `(f 3)` and `(f 3.0)` are
+/// separate calls.
+fn memoize(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let f = function_arg("memoize", &args[0])?.clone();
+    let cache: Rc<RefCell<HashMap<CacheKey, Value>>> = Rc::default();
+    Ok(native(
+        "memoize",
+        &["&", "args"],
+        move |args, scope, ctx| {
+            let key: Option<CacheKey> = args
+                .iter()
+                .map(|arg| Some((arg.type_name(), arg.to_map_key().ok()?)))
+                .collect();
+            if let Some(value) = key
+                .as_ref()
+                .and_then(|key| cache.borrow().get(key).cloned())
+            {
+                return Ok(value);
+            }
+            let value = f.call("memoize", args, scope, ctx)?;
+            if let Some(key) = key {
+                cache.borrow_mut().insert(key, value.clone());
+            }
+            Ok(value)
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    #[test]
+    fn test_apply_native() {
+        assert_eq!(run("(apply + [1 2 3])"), Ok(Value::Integer(6)));
+        assert_eq!(run("(apply + [])"), Ok(Value::Integer(0)));
+    }
+
+    #[test]
+    fn test_apply_user_defined() {
+        assert_eq!(
+            run("(defn pick-second [a b] b) (apply pick-second [1 2])"),
+            Ok(Value::Integer(2))
+        );
+        assert!(run("(defn pick-second [a b] b) (apply pick-second [1])").is_err());
+    }
+
+    #[test]
+    fn test_apply_spread_arguments() {
+        assert_eq!(run("(apply * 2 3 [4])"), Ok(Value::Integer(24)));
+        assert_eq!(
+            run("(apply str \"a\" [\"b\" \"c\"])"),
+            Ok(Value::String("abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_apply_invalid_arguments() {
+        assert!(run("(apply +)").is_err());
+        assert!(run("(apply + 1)").is_err());
+        assert!(run("(apply 1 [1])").is_err());
+    }
+
+    #[test]
+    fn test_identity() {
+        assert_eq!(run("(identity 5)"), Ok(Value::Integer(5)));
+        assert_eq!(run("(map->list (identity {:a 1}))"), run("[:a 1]"));
+    }
+
+    #[test]
+    fn test_constantly() {
+        assert_eq!(
+            run("(def seven (constantly 7)) (seven)"),
+            Ok(Value::Integer(7))
+        );
+        assert_eq!(
+            run("(def f (constantly :x)) [(f) (f 1 2 3)]"),
+            run("[:x :x]")
+        );
+    }
+
+    #[test]
+    fn test_comp() {
+        assert_eq!(
+            run("(def f (comp (fn [x] (* x 2)) (fn [x] (+ x 1)))) (f 5)"),
+            Ok(Value::Integer(12))
+        );
+        assert_eq!(
+            run("(def g (comp str +)) (g 1 2 3)"),
+            Ok(Value::String("6".to_string()))
+        );
+        assert!(run("(comp 1 str)").is_err());
+        assert!(run("(def h (comp (fn [x] x) (fn [x] x))) (h 1 2)").is_err());
+    }
+
+    #[test]
+    fn test_partial() {
+        assert_eq!(
+            run("(def add5 (partial + 5)) (add5 10)"),
+            Ok(Value::Integer(15))
+        );
+        assert_eq!(
+            run("(def greet (partial str \"hi \" \"there\")) (greet \"!\")"),
+            Ok(Value::String("hi there!".to_string()))
+        );
+        assert_eq!(
+            run("(def f (partial (fn [a b c] [a b c]) 1 2)) (f 3)"),
+            run("[1 2 3]")
+        );
+        assert!(run("(partial 1 2)").is_err());
+    }
+
+    #[test]
+    fn test_partial_arity_checked_on_call() {
+        let source = "(def f (partial (fn [a b] b) 1 2 3))";
+        assert!(run(source).is_ok());
+        assert_eq!(
+            run(&format!("{} (f)", source)),
+            Err(
+                "EvaluationError: Function partial takes 2 arguments, but 3 were given".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_memoize() {
+        assert_eq!(
+            run("(def calls 0)
+                 (def fib (memoize (fn [n]
+                   (set! calls (+ calls 1))
+                   (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))))
+                 [(fib 60) calls (fib 60) (fib 30) calls]"),
+            run("[1548008755920 61 1548008755920 832040 61]")
+        );
+        assert_eq!(
+            run("(def calls 0)
+                 (def f (memoize (fn [& xs] (set! calls (+ calls 1)) (count xs))))
+                 [(f 1 2) (f 1 2) (f 2 1) (f) (f) (f [1]) (f [1]) calls]"),
+            run("[2 2 2 0 0 1 1 5]")
+        );
+        assert_eq!(
+            run("(def f (memoize (fn [x] (* x 2)))) [(type (f 3)) (type (f 3.0)) (type (f 3))]"),
+            run("[\"integer\" \"float\" \"integer\"]")
+        );
+    }
+
+    #[test]
+    fn test_memoize_errors() {
+        assert_eq!(
+            run("(memoize 1)"),
+            Err("EvaluationError: memoize expects a function, got integer 1".to_string())
+        );
+        assert_eq!(
+            run("(def calls 0)
+                 (def f (memoize (fn [x] (set! calls (+ calls 1)) (error \"no\"))))
+                 (try (f 1) (catch e nil))
+                 (try (f 1) (catch e nil))
+                 calls"),
+            Ok(Value::Integer(2))
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use super::super::interpretator::{Cast, Context, Scope};
+use super::super::nodes::*;
+use super::{define, list_value};
+
+pub fn register(scope: &Scope) {
+    define(scope, "print", &["&", "values"], print);
+    define(scope, "println", &["&", "values"], println);
+    define(scope, "read-line", &[], read_line);
+    define(scope, "read-number", &[], read_number);
+    define(scope, "now", &[], now);
+    define(scope, "env", &["name"], env);
+    define(scope, "args", &[], args);
+    define(scope, "slurp", &["path"], slurp);
+    define(scope, "spit", &["path", "content"], spit);
+}
+
+fn write_values(args: &[Value], end: &str, ctx: &mut Context) -> Result<Value, String> {
+    let mut parts = vec![];
+    for arg in args {
+        parts.push(arg.cast_to_string().map_err(|e| e.to_string())?);
+    }
+    write!(ctx.output, "{}{}", parts.join(" "), end)
+        .and_then(|_| ctx.output.flush())
+        .map_err(|e| format!("failed to write output: {}", e))?;
+    Ok(Value::Null)
+}
+
+/// `(print values...)` writes the values separated by spaces.
+fn print(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    write_values(&args, "", ctx)
+}
+
+/// `(println values...)` is `print` followed by a newline.
+fn println(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    write_values(&args, "\n", ctx)
+}
+
+/// Reads the next line of input without its line ending, or None at the
+/// end of the input.
+fn next_line(ctx: &mut Context) -> Result<Option<String>, String> {
+    let mut line = String::new();
+    let read = ctx
+        .input
+        .read_line(&mut line)
+        .map_err(|e| format!("failed to read input: {}", e))?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+/// `(read-line)` returns the next line of input, or null at the end.
+fn read_line(_: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    Ok(match next_line(ctx)? {
+        Some(line) => Value::String(line),
+        None => Value::Null,
+    })
+}
+
+/// `(read-number)` reads the next line as a number, or returns null at the
+/// end of the input.
+fn read_number(_: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    match next_line(ctx)? {
+        Some(line) => match (line.trim().parse::<i64>(), line.trim().parse::<f64>()) {
+            (Ok(i), _) => Ok(Value::Integer(i)),
+            (_, Ok(n)) => Ok(Value::Number(n)),
+            _ => Err(format!("read-number got {:?}, which is not a number", line)),
+        },
+        None => Ok(Value::Null),
+    }
+}
+
+/// `(now)` returns the current Unix time in seconds.
+fn now(_: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    Ok(Value::Number(ctx.clock.now()))
+}
+
+/// `(env name)` returns the value of the environment variable `name`, or
+/// null when it is not set.
+fn env(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let name = match &args[0] {
+        Value::String(name) => name,
+        v => return Err(format!("env expects a string, got {} {}", v.type_name(), v)),
+    };
+    Ok(ctx.env.var(name).map(Value::String).unwrap_or(Value::Null))
+}
+
+/// `(args)` returns the command line arguments as a list of strings.
+fn args(_: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    Ok(list_value(
+        ctx.args.iter().cloned().map(Value::String).collect(),
+    ))
+}
+
+/// The path argument of the file native `name`, which fails unless
+/// filesystem access was enabled.
+fn path_arg<'v>(name: &str, value: &'v Value, ctx: &Context) -> Result<&'v str, String> {
+    if !ctx.filesystem_access {
+        return Err(format!(
+            "{} is not allowed: filesystem access is disabled",
+            name
+        ));
+    }
+    match value {
+        Value::String(path) => Ok(path),
+        v => Err(format!(
+            "{} expects a path string, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+/// `(slurp path)` returns the contents of the file at `path`.
+fn slurp(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let path = path_arg("slurp", &args[0], ctx)?;
+    std::fs::read_to_string(path)
+        .map(Value::String)
+        .map_err(|e| format!("slurp failed to read {}: {}", path, e))
+}
+
+/// `(spit path content)` writes `content` to the file at `path`, replacing
+/// whatever it held.
+fn spit(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let path = path_arg("spit", &args[0], ctx)?;
+    let content = args[1].cast_to_string().map_err(|e| e.to_string())?;
+    std::fs::write(path, content).map_err(|e| format!("spit failed to write {}: {}", path, e))?;
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::interpretator::{Clock, Interpretator};
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run_captured(source: &str) -> (Value, Vec<u8>) {
+        let buffer = SharedBuffer::default();
+        let mut interpretator = Interpretator::new(None).with_output(Box::new(buffer.clone()));
+        let value = interpretator.run(source.to_owned()).unwrap();
+        let output = buffer.0.borrow().clone();
+        (value, output)
+    }
+
+    #[test]
+    fn test_println() {
+        let (value, output) = run_captured("(println \"hello\") (println 1 true)");
+        assert_eq!(value, Value::Null);
+        assert_eq!(output, b"hello\n1 true\n".to_vec());
+    }
+
+    #[test]
+    fn test_print() {
+        let (_, output) = run_captured("(print \"a\") (print \"b\" 2)");
+        assert_eq!(output, b"ab 2".to_vec());
+    }
+
+    #[test]
+    fn test_function_bodies_run_every_form() {
+        let (value, output) = run_captured(
+            "(def calls 0)
+             (defn greet [name] (println \"hello\" name) (set! calls (+ calls 1)) (str \"hi \" name))
+             (def shout (fn [s] (print s) (print \"!\") (upper s)))
+             [(greet \"bob\") (shout \"hey\") calls]",
+        );
+        assert_eq!(
+            value,
+            Value::List(vec![
+                Node::Atom(Value::String("hi bob".to_string())),
+                Node::Atom(Value::String("HEY".to_string())),
+                Node::Atom(Value::Integer(1)),
+            ])
+        );
+        assert_eq!(output, b"hello bob\nhey!".to_vec());
+    }
+
+    #[test]
+    fn test_with_output_to_string() {
+        let (value, output) = run_captured(
+            "(print \"a\")
+             (def s (with-output-to-string (print \"b\") (println 1 2) 3))
+             (print \"c\")
+             s",
+        );
+        assert_eq!(value, Value::String("b1 2\n".to_string()));
+        assert_eq!(output, b"ac".to_vec());
+    }
+
+    #[test]
+    fn test_with_output_to_string_nested() {
+        let (value, output) = run_captured(
+            "[(with-output-to-string
+               (print \"x\")
+               (print (str \"<\" (with-output-to-string (print \"y\")) \">\"))
+               (print \"z\"))
+              (with-output-to-string)]",
+        );
+        assert_eq!(
+            value,
+            Value::List(vec![
+                Node::Atom(Value::String("x<y>z".to_string())),
+                Node::Atom(Value::String("".to_string())),
+            ])
+        );
+        assert_eq!(output, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_with_output_to_string_restores_on_error() {
+        let (value, output) = run_captured(
+            "(try (with-output-to-string (print \"lost\") (error \"boom\"))
+               (catch e (print \"caught\") e))",
+        );
+        assert_eq!(value, Value::String("boom".to_string()));
+        assert_eq!(output, b"caught".to_vec());
+    }
+
+    fn run_with_input(source: &str, input: &str) -> Result<Value, String> {
+        let input = io::Cursor::new(input.as_bytes().to_vec());
+        Interpretator::new(None)
+            .with_input(Box::new(input))
+            .run(source.to_owned())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_read_line() {
+        assert_eq!(
+            run_with_input(
+                "[(read-line) (read-line) (read-line) (read-line)]",
+                "first\nsecond\r\n\n"
+            ),
+            run_with_input("[\"first\" \"second\" \"\" nil]", "")
+        );
+        assert_eq!(
+            run_with_input("(read-line)", "no newline"),
+            Ok(Value::String("no newline".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_read_number() {
+        assert_eq!(
+            run_with_input("(+ (read-number) (read-number))", " 2\n-3.5\n"),
+            Ok(Value::Number(-1.5))
+        );
+        assert_eq!(run_with_input("(read-number)", ""), Ok(Value::Null));
+        assert_eq!(
+            run_with_input("(read-number)", "abc\n"),
+            Err("EvaluationError: read-number got \"abc\", which is not a number".to_string())
+        );
+    }
+
+    /// A clock that starts at `start` and advances by `step` seconds every
+    /// time it is read.
+    struct FakeClock {
+        time: Cell<f64>,
+        step: f64,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> f64 {
+            let time = self.time.get();
+            self.time.set(time + self.step);
+            time
+        }
+    }
+
+    fn fake_clock(start: f64, step: f64) -> Box<FakeClock> {
+        Box::new(FakeClock {
+            time: Cell::new(start),
+            step,
+        })
+    }
+
+    #[test]
+    fn test_now() {
+        let mut interpretator = Interpretator::new(None).with_clock(fake_clock(1700000000.5, 2.0));
+        assert_eq!(
+            interpretator.run("[(now) (now)]".to_owned()).unwrap(),
+            Value::List(vec![
+                Node::Atom(Value::Number(1700000000.5)),
+                Node::Atom(Value::Number(1700000002.5)),
+            ])
+        );
+        assert!(matches!(
+            Interpretator::new(None).run("(now)".to_owned()),
+            Ok(Value::Number(n)) if n > 1.6e9
+        ));
+    }
+
+    #[test]
+    fn test_env() {
+        let mut vars = HashMap::new();
+        vars.insert("HOME".to_string(), "/home/lisp".to_string());
+        vars.insert("EMPTY".to_string(), String::new());
+        let mut interpretator = Interpretator::new(None).with_env(Box::new(vars));
+        let mut run = |source: &str| {
+            interpretator
+                .run(source.to_owned())
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            run("(env \"HOME\")"),
+            Ok(Value::String("/home/lisp".to_string()))
+        );
+        assert_eq!(run("(env \"EMPTY\")"), Ok(Value::String(String::new())));
+        assert_eq!(run("(env \"PATH\")"), Ok(Value::Null));
+        assert_eq!(
+            run("(env :home)"),
+            Err("EvaluationError: env expects a string, got keyword :home".to_string())
+        );
+    }
+
+    #[test]
+    fn test_args() {
+        let mut interpretator = Interpretator::new(None)
+            .with_args(vec!["input.txt".to_string(), "--verbose".to_string()]);
+        assert_eq!(
+            interpretator.run("(args)".to_owned()).unwrap(),
+            Value::List(vec![
+                Node::Atom(Value::String("input.txt".to_string())),
+                Node::Atom(Value::String("--verbose".to_string())),
+            ])
+        );
+        assert_eq!(
+            Interpretator::new(None).run("(args)".to_owned()).unwrap(),
+            Value::List(vec![])
+        );
+    }
+
+    #[test]
+    fn test_slurp_spit() {
+        let path = std::env::temp_dir().join(format!("lisp-rust-spit-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut interpretator = Interpretator::new(None).with_filesystem_access(true);
+        let source = format!(
+            "(spit {:?} \"line 1\nline 2\") (spit {:?} (str (slurp {:?}) \"!\")) (slurp {:?})",
+            path, path, path, path
+        );
+        let result = interpretator.run(source).map_err(|e| e.to_string());
+        let written = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result, Ok(Value::String("line 1\nline 2!".to_string())));
+        assert_eq!(written.unwrap(), "line 1\nline 2!");
+        assert!(interpretator
+            .run(format!("(slurp {:?})", path))
+            .unwrap_err()
+            .to_string()
+            .starts_with(&format!("EvaluationError: slurp failed to read {}: ", path)));
+    }
+
+    #[test]
+    fn test_slurp_spit_disabled() {
+        let path =
+            std::env::temp_dir().join(format!("lisp-rust-denied-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut interpretator = Interpretator::new(None);
+        assert_eq!(
+            interpretator
+                .run(format!("(spit {:?} \"x\")", path))
+                .map_err(|e| e.to_string()),
+            Err("EvaluationError: spit is not allowed: filesystem access is disabled".to_string())
+        );
+        assert!(!std::path::Path::new(&path).exists());
+        assert_eq!(
+            interpretator
+                .run(format!("(slurp {:?})", path))
+                .map_err(|e| e.to_string()),
+            Err("EvaluationError: slurp is not allowed: filesystem access is disabled".to_string())
+        );
+    }
+
+    #[test]
+    fn test_time() {
+        let buffer = SharedBuffer::default();
+        let mut interpretator = Interpretator::new(None)
+            .with_output(Box::new(buffer.clone()))
+            .with_clock(fake_clock(10.0, 0.25));
+        assert_eq!(
+            interpretator.run("(time (+ 1 2))".to_owned()).unwrap(),
+            Value::Integer(3)
+        );
+        assert_eq!(
+            buffer.0.borrow().clone(),
+            b"Elapsed time: 250 ms\n".to_vec()
+        );
+        assert!(interpretator.run("(time)".to_owned()).is_err());
+        assert!(interpretator.run("(time (undefined))".to_owned()).is_err());
+    }
+}
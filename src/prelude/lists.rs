@@ -0,0 +1,1113 @@
+use super::super::interpretator::{Cast, Context, Scope};
+use super::super::nodes::*;
+use super::super::special_forms::is_truthy;
+use super::{define, function_arg, index_arg, list_arg, list_value};
+use std::cmp::Ordering;
+
+pub fn register(scope: &Scope) {
+    define(scope, "sort", &["list"], sort);
+    define(scope, "sort-by", &["f", "list"], sort_by);
+    define(scope, "map", &["f", "collection"], map);
+    define(scope, "filter", &["pred", "collection"], filter);
+    define(scope, "reduce", &["f", "init", "list"], reduce);
+    define(scope, "fold-right", &["f", "init", "list"], fold_right);
+    define(scope, "scan", &["f", "init", "list"], scan);
+    define(scope, "range", &["x", "&", "bounds"], range);
+    define(scope, "list", &["&", "values"], list);
+    define(scope, "seq", &["collection"], seq);
+    define(scope, "into", &["target", "collection"], into);
+    define(scope, "repeat", &["n", "value"], repeat);
+    define(scope, "cycle", &["n", "list"], cycle);
+    define(scope, "nth", &["list", "index"], nth);
+    define(scope, "first", &["list"], first);
+    define(scope, "second", &["list"], second);
+    define(scope, "append", &["&", "lists"], append);
+    define(scope, "concat", &["&", "lists"], append);
+    define(scope, "reverse", &["list"], reverse);
+    define(scope, "flatten", &["list"], flatten);
+    define(scope, "distinct", &["list"], distinct);
+    define(scope, "dedupe", &["list"], dedupe);
+    define(scope, "flatten-depth", &["list", "n"], flatten_depth);
+    define(scope, "take", &["n", "list"], take);
+    define(scope, "drop", &["n", "list"], drop);
+    define(scope, "slice", &["list", "start", "end"], slice);
+    define(scope, "count", &["collection"], count);
+    define(scope, "empty?", &["collection"], is_empty);
+    define(scope, "contains?", &["collection", "value"], contains);
+    define(scope, "index-of", &["collection", "value"], index_of);
+    define(scope, "some", &["pred", "list"], some);
+    define(scope, "every", &["pred", "list"], every);
+}
+
+/// Stable sort of `values` by `keys`, failing if two keys have no ordering
+/// instead of leaving the result order unspecified. Every key is checked
+/// against the first one before sorting, so the keys `sort_by` sees are
+/// known to be ordered among themselves.
+fn sort_values_by_keys(values: Vec<Value>, keys: Vec<Value>) -> Result<Value, String> {
+    if let Some((first, rest)) = keys.split_first() {
+        for key in rest {
+            compare_keys(first, key)?;
+        }
+    }
+    let mut pairs: Vec<(Value, Value)> = keys.into_iter().zip(values).collect();
+    pairs.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    Ok(list_value(pairs.into_iter().map(|(_, v)| v).collect()))
+}
+
+fn compare_keys(a: &Value, b: &Value) -> Result<Ordering, String> {
+    a.partial_cmp(b).ok_or_else(|| {
+        format!(
+            "cannot compare {} {} and {} {}",
+            a.type_name(),
+            a,
+            b.type_name(),
+            b
+        )
+    })
+}
+
+/// `(sort list)` returns a new list sorted in ascending order.
+fn sort(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let values = list_arg("sort", &args[0])?;
+    sort_values_by_keys(values.clone(), values)
+}
+
+/// `(sort-by f list)` sorts by the result of calling `f` on each element.
+fn sort_by(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let f = function_arg("sort-by", &args[0])?;
+    let values = list_arg("sort-by", &args[1])?;
+    let mut keys = vec![];
+    for value in &values {
+        keys.push(f.call("sort-by", vec![value.clone()], scope, ctx)?);
+    }
+    sort_values_by_keys(values, keys)
+}
+
+/// `(reduce f init list)` folds from the left, calling `(f acc element)`
+/// for each element in order.
+fn reduce(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let f = function_arg("reduce", &args[0])?;
+    let mut acc = args[1].clone();
+    for value in list_arg("reduce", &args[2])? {
+        acc = f.call("reduce", vec![acc, value], scope, ctx)?;
+    }
+    Ok(acc)
+}
+
+/// `(fold-right f init list)` folds from the right, calling
+/// `(f element acc)` from the last element to the first, so
+/// `(fold-right f init [a b])` is `(f a (f b init))`.
+fn fold_right(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let f = function_arg("fold-right", &args[0])?;
+    let mut acc = args[1].clone();
+    for value in list_arg("fold-right", &args[2])?.into_iter().rev() {
+        acc = f.call("fold-right", vec![value, acc], scope, ctx)?;
+    }
+    Ok(acc)
+}
+
+/// `(scan f init list)` is like `reduce` with `(f acc element)`, but returns
+/// every accumulator value, starting with `init`.
+fn scan(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let f = function_arg("scan", &args[0])?;
+    let mut acc = args[1].clone();
+    let mut values = vec![acc.clone()];
+    for value in list_arg("scan", &args[2])? {
+        acc = f.call("scan", vec![acc, value], scope, ctx)?;
+        values.push(acc.clone());
+        ctx.check_collection_size(values.len())?;
+    }
+    Ok(list_value(values))
+}
+
+fn list(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    ctx.check_collection_size(args.len())?;
+    Ok(list_value(args))
+}
+
+/// Returns element `index` of `list`, failing when it is out of bounds.
+fn element(name: &str, list: &Value, index: usize) -> Result<Value, String> {
+    let values = list_arg(name, list)?;
+    match values.into_iter().nth(index) {
+        Some(v) => Ok(v),
+        None => Err(format!("{} index {} is out of bounds", name, index)),
+    }
+}
+
+fn nth(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let index = index_arg("nth", &args[1])?;
+    element("nth", &args[0], index)
+}
+
+fn first(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    element("first", &args[0], 0)
+}
+
+fn second(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    element("second", &args[0], 1)
+}
+
+/// `(append lists...)` joins the lists into one.
+fn append(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let mut values = vec![];
+    for arg in &args {
+        values.extend(list_arg("append", arg)?);
+        ctx.check_collection_size(values.len())?;
+    }
+    Ok(list_value(values))
+}
+
+fn reverse(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut values = list_arg("reverse", &args[0])?;
+    values.reverse();
+    Ok(list_value(values))
+}
+
+/// `(flatten list)` splices every nested list into the result, however
+/// deeply it is nested. Other values are kept as they are.
+fn flatten(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut values = vec![];
+    flatten_into("flatten", &args[0], None, &mut values)?;
+    Ok(list_value(values))
+}
+
+/// `(flatten-depth list n)` is `flatten` that only splices lists nested at
+/// most `n` levels deep, so `(flatten-depth list 0)` returns the list as
+/// is.
+fn flatten_depth(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let depth = index_arg("flatten-depth", &args[1])?;
+    let mut values = vec![];
+    flatten_into("flatten-depth", &args[0], Some(depth), &mut values)?;
+    Ok(list_value(values))
+}
+
+/// Pushes the elements of `list` to `out`, splicing nested lists in up to
+/// `depth` levels, or all of them if `depth` is `None`.
+fn flatten_into(
+    name: &str,
+    list: &Value,
+    depth: Option<usize>,
+    out: &mut Vec<Value>,
+) -> Result<(), String> {
+    for value in list_arg(name, list)? {
+        match value {
+            nested @ Value::List(_) if depth != Some(0) => {
+                flatten_into(name, &nested, depth.map(|d| d - 1), out)?
+            }
+            value => out.push(value),
+        }
+    }
+    Ok(())
+}
+
+/// `(distinct list)` removes every element `equal?` to an earlier one,
+/// keeping the first occurrences in order.
+fn distinct(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut values: Vec<Value> = vec![];
+    for value in list_arg("distinct", &args[0])? {
+        if !values.iter().any(|v| v.structurally_equals(&value)) {
+            values.push(value);
+        }
+    }
+    Ok(list_value(values))
+}
+
+/// `(dedupe list)` collapses runs of consecutive `equal?` elements into
+/// one.
+fn dedupe(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut values = list_arg("dedupe", &args[0])?;
+    values.dedup_by(|a, b| a.structurally_equals(b));
+    Ok(list_value(values))
+}
+
+/// `(take n list)` returns the first `n` elements, or all of them when the
+/// list is shorter.
+fn take(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let n = index_arg("take", &args[0])?;
+    let values = list_arg("take", &args[1])?;
+    Ok(list_value(values.into_iter().take(n).collect()))
+}
+
+/// `(drop n list)` returns the list without its first `n` elements.
+fn drop(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let n = index_arg("drop", &args[0])?;
+    let values = list_arg("drop", &args[1])?;
+    Ok(list_value(values.into_iter().skip(n).collect()))
+}
+
+/// `(slice list start end)` returns the elements in `start..end`.
+fn slice(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let values = list_arg("slice", &args[0])?;
+    let start = index_arg("slice", &args[1])?;
+    let end = index_arg("slice", &args[2])?;
+    if start > end || end > values.len() {
+        return Err(format!(
+            "slice range {}..{} is out of bounds for length {}",
+            start,
+            end,
+            values.len()
+        ));
+    }
+    Ok(list_value(values[start..end].to_vec()))
+}
+
+/// The number of elements of a list or map, or characters of a string.
+fn collection_size(name: &str, value: &Value) -> Result<usize, String> {
+    match value {
+        Value::List(l) => Ok(l.len()),
+        Value::Map(m) => Ok(m.len()),
+        Value::String(s) => Ok(s.chars().count()),
+        v => Err(format!(
+            "{} expects a list, map or string, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+fn count(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Integer(collection_size("count", &args[0])? as i64))
+}
+
+fn is_empty(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(collection_size("empty?", &args[0])? == 0))
+}
+
+/// The index of the first element of a list equal to `value`, or the
+/// character index of the first occurrence of `value` in a string.
+fn position(name: &str, collection: &Value, value: &Value) -> Result<Option<usize>, String> {
+    match (collection, value) {
+        (Value::List(_), _) => Ok(list_arg(name, collection)?
+            .iter()
+            .position(|element| element == value)),
+        (Value::String(s), Value::String(needle)) => Ok(s
+            .find(needle.as_str())
+            .map(|byte| s[..byte].chars().count())),
+        (Value::String(s), Value::Char(c)) => Ok(s.chars().position(|ch| ch == *c)),
+        (Value::String(_), v) => Err(format!(
+            "{} cannot search a string for {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+        (v, _) => Err(format!(
+            "{} expects a list or string, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+/// `(contains? collection value)` is true when a list has an element
+/// `equal?` to `value`, or a string contains `value` as a substring.
+fn contains(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(
+        position("contains?", &args[0], &args[1])?.is_some(),
+    ))
+}
+
+/// `(index-of collection value)` returns the index of the first match, or
+/// -1 when there is none.
+fn index_of(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Integer(
+        match position("index-of", &args[0], &args[1])? {
+            Some(index) => index as i64,
+            None => -1,
+        },
+    ))
+}
+
+/// `(map f collection)` returns the results of calling `f` on each element
+/// of any collection `seq` accepts.
+fn map(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let f = function_arg("map", &args[0])?;
+    let mut results = vec![];
+    for value in seq_arg("map", &args[1])? {
+        results.push(f.call("map", vec![value], scope, ctx)?);
+    }
+    Ok(list_value(results))
+}
+
+/// `(filter pred collection)` returns the elements of any collection `seq`
+/// accepts that `pred` is truthy for, in order.
+fn filter(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let pred = function_arg("filter", &args[0])?;
+    let mut kept = vec![];
+    for value in seq_arg("filter", &args[1])? {
+        if is_truthy(&pred.call("filter", vec![value.clone()], scope, ctx)?)? {
+            kept.push(value);
+        }
+    }
+    Ok(list_value(kept))
+}
+
+/// `(some pred list)` returns the first truthy result of calling `pred` on
+/// the elements in order, or null if there is none. Elements after it are
+/// not looked at.
+fn some(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let pred = function_arg("some", &args[0])?;
+    for value in list_arg("some", &args[1])? {
+        let result = pred.call("some", vec![value], scope, ctx)?;
+        if is_truthy(&result)? {
+            return Ok(result);
+        }
+    }
+    Ok(Value::Null)
+}
+
+/// `(every pred list)` returns whether `pred` is truthy for every element,
+/// stopping at the first one it is not.
+fn every(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let pred = function_arg("every", &args[0])?;
+    for value in list_arg("every", &args[1])? {
+        if !is_truthy(&pred.call("every", vec![value], scope, ctx)?)? {
+            return Ok(Value::Boolean(false));
+        }
+    }
+    Ok(Value::Boolean(true))
+}
+
+/// `(range end)`, `(range start end)` and `(range start end step)` return
+/// the numbers from `start` (default 0) up to but excluding `end`, counting
+/// by `step` (default 1). A negative step counts down. The numbers are
+/// integers when all the arguments are.
+fn range(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let integers = args.iter().map(|arg| match arg {
+        Value::Integer(i) => Some(*i),
+        _ => None,
+    });
+    if let Some(bounds) = integers.collect::<Option<Vec<_>>>() {
+        return integer_range(&bounds, ctx);
+    }
+    let mut bounds = vec![];
+    for arg in &args {
+        bounds.push(arg.cast_to_number().map_err(|e| e.to_string())?);
+    }
+    let (start, end, step) = match bounds[..] {
+        [end] => (0.0, end, 1.0),
+        [start, end] => (start, end, 1.0),
+        [start, end, step] => (start, end, step),
+        _ => return Err("range expects between 1 and 3 arguments".to_string()),
+    };
+    if step == 0.0 {
+        return Err("range step cannot be zero".to_string());
+    }
+    let mut values = vec![];
+    let mut n = start;
+    while (step > 0.0 && n < end) || (step < 0.0 && n > end) {
+        values.push(Value::Number(n));
+        ctx.check_collection_size(values.len())?;
+        n = start + values.len() as f64 * step;
+    }
+    Ok(list_value(values))
+}
+
+/// `range` over integer bounds, counted in integers so that large bounds
+/// stay exact. A step past the largest or smallest integer is also past
+/// `end`, so it ends the range.
+fn integer_range(bounds: &[i64], ctx: &mut Context) -> Result<Value, String> {
+    let (start, end, step) = match *bounds {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => return Err("range expects between 1 and 3 arguments".to_string()),
+    };
+    if step == 0 {
+        return Err("range step cannot be zero".to_string());
+    }
+    let mut values = vec![];
+    let mut next = Some(start);
+    while let Some(n) = next.filter(|n| (step > 0 && *n < end) || (step < 0 && *n > end)) {
+        values.push(Value::Integer(n));
+        ctx.check_collection_size(values.len())?;
+        next = n.checked_add(step);
+    }
+    Ok(list_value(values))
+}
+
+/// `(seq collection)` returns the elements of a list, the chars of a
+/// string or the `[key value]` entries of a map, ordered by key, as a list.
+fn seq(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    seq_arg("seq", &args[0]).map(list_value)
+}
+
+/// The elements of an argument of the native `name` that `seq` accepts.
+fn seq_arg(name: &str, value: &Value) -> Result<Vec<Value>, String> {
+    value.as_seq().ok_or_else(|| {
+        format!(
+            "{} expects a collection, got {} {}",
+            name,
+            value.type_name(),
+            value
+        )
+    })
+}
+
+/// `(into target collection)` adds the elements of any collection `seq`
+/// accepts to `target`. A list target gets them appended, and a map target
+/// takes them as `[key value]` entries or pairs, later ones winning.
+fn into(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let values = match args[1].as_seq() {
+        Some(values) => values,
+        None => {
+            return Err(format!(
+                "into expects a collection, got {} {}",
+                args[1].type_name(),
+                args[1]
+            ))
+        }
+    };
+    match &args[0] {
+        Value::List(_) => {
+            let mut list = list_arg("into", &args[0])?;
+            list.extend(values);
+            ctx.check_collection_size(list.len())?;
+            Ok(list_value(list))
+        }
+        Value::Map(map) => {
+            let mut map = map.clone();
+            for value in values {
+                let (key, value) = match (&value, value.as_seq()) {
+                    (Value::List(_), Some(entry)) if entry.len() == 2 => {
+                        (entry[0].clone(), entry[1].clone())
+                    }
+                    (Value::Pair(key, value), _) => match (&**key, &**value) {
+                        (Node::Atom(key), Node::Atom(value)) => (key.clone(), value.clone()),
+                        _ => return Err("into got an unevaluated pair".to_string()),
+                    },
+                    _ => {
+                        return Err(format!(
+                            "into expects [key value] entries for a map, got {} {}",
+                            value.type_name(),
+                            value
+                        ))
+                    }
+                };
+                map.insert(key.to_map_key()?, value);
+            }
+            ctx.check_collection_size(map.len())?;
+            Ok(Value::Map(map))
+        }
+        v => Err(format!(
+            "into expects a list or a map to add to, got {} {}",
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+/// `(repeat n value)` returns a list of `n` copies of `value`.
+fn repeat(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let n = index_arg("repeat", &args[0])?;
+    ctx.check_collection_size(n)?;
+    Ok(list_value(vec![args[1].clone(); n]))
+}
+
+/// `(cycle n list)` returns the first `n` elements of the list repeated
+/// over and over.
+fn cycle(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let n = index_arg("cycle", &args[0])?;
+    let values = list_arg("cycle", &args[1])?;
+    if n == 0 {
+        return Ok(list_value(vec![]));
+    }
+    if values.is_empty() {
+        return Err("cycle expects a non-empty list".to_string());
+    }
+    ctx.check_collection_size(n)?;
+    Ok(list_value(values.into_iter().cycle().take(n).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::interpretator::Interpretator;
+    use super::super::run;
+    use super::*;
+
+    fn numbers(values: &[f64]) -> Value {
+        list_value(values.iter().map(|n| Value::Number(*n)).collect())
+    }
+
+    fn integers(values: &[i64]) -> Value {
+        list_value(values.iter().map(|i| Value::Integer(*i)).collect())
+    }
+
+    fn strings(values: &[&str]) -> Value {
+        list_value(
+            values
+                .iter()
+                .map(|s| Value::String(s.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_sort_numbers() {
+        assert_eq!(
+            run("(sort [3 -1 2.5 0])"),
+            Ok(list_value(vec![
+                Value::Integer(-1),
+                Value::Integer(0),
+                Value::Number(2.5),
+                Value::Integer(3)
+            ]))
+        );
+        assert_eq!(run("(sort [])"), Ok(numbers(&[])));
+    }
+
+    #[test]
+    fn test_sort_strings() {
+        assert_eq!(
+            run("(sort [\"pear\" \"apple\" \"fig\"])"),
+            Ok(strings(&["apple", "fig", "pear"]))
+        );
+    }
+
+    #[test]
+    fn test_sort_does_not_modify_input() {
+        assert_eq!(run("(def l [2 1]) (sort l) l"), Ok(integers(&[2, 1])));
+    }
+
+    #[test]
+    fn test_sort_mixed_types() {
+        assert_eq!(
+            run("(sort [1 \"a\"])"),
+            Err("EvaluationError: cannot compare integer 1 and string \"a\"".to_string())
+        );
+        let mixed = (0..30)
+            .map(|i| format!("{} \"{}\"", i, i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(
+            run(&format!("(sort [{}])", mixed)),
+            Err("EvaluationError: cannot compare integer 0 and string \"0\"".to_string())
+        );
+        assert!(run(&format!("(sort-by (fn [x] x) [{}])", mixed)).is_err());
+    }
+
+    #[test]
+    fn test_sort_nan() {
+        let values = vec![
+            Value::Number(1.0),
+            Value::Number(f64::NAN),
+            Value::Number(2.0),
+        ];
+        assert_eq!(
+            sort_values_by_keys(values.clone(), values),
+            Err("cannot compare float 1.0 and float NaN".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sort_by() {
+        assert_eq!(
+            run("(sort-by (fn [s] (length s)) [\"ccc\" \"a\" \"bb\" \"d\"])"),
+            Ok(strings(&["a", "d", "bb", "ccc"]))
+        );
+        assert!(run("(sort-by 1 [1 2])").is_err());
+    }
+
+    #[test]
+    fn test_range_end() {
+        assert_eq!(run("(range 4)"), Ok(integers(&[0, 1, 2, 3])));
+        assert_eq!(run("(range 0)"), Ok(numbers(&[])));
+    }
+
+    #[test]
+    fn test_range_start_end() {
+        assert_eq!(run("(range 2 5)"), Ok(integers(&[2, 3, 4])));
+        assert_eq!(run("(range 5 2)"), Ok(numbers(&[])));
+    }
+
+    #[test]
+    fn test_range_step() {
+        assert_eq!(run("(range 0 10 3)"), Ok(integers(&[0, 3, 6, 9])));
+        assert_eq!(
+            run("(range 0 1 0.25)"),
+            Ok(numbers(&[0.0, 0.25, 0.5, 0.75]))
+        );
+    }
+
+    #[test]
+    fn test_range_descending() {
+        assert_eq!(run("(range 3 0 -1)"), Ok(integers(&[3, 2, 1])));
+        assert_eq!(run("(range 0 3 -1)"), Ok(numbers(&[])));
+    }
+
+    #[test]
+    fn test_range_large_integers() {
+        assert_eq!(
+            run("(range 9007199254740993 9007199254740996)"),
+            Ok(list_value(vec![
+                Value::Integer(9007199254740993),
+                Value::Integer(9007199254740994),
+                Value::Integer(9007199254740995),
+            ]))
+        );
+        assert_eq!(
+            run("(range 9223372036854775805 9223372036854775807 2)"),
+            Ok(list_value(vec![Value::Integer(9223372036854775805)]))
+        );
+        assert_eq!(
+            run("(range -9223372036854775807 -9223372036854775808 -5)"),
+            Ok(list_value(vec![Value::Integer(-9223372036854775807)]))
+        );
+    }
+
+    #[test]
+    fn test_range_invalid() {
+        assert_eq!(
+            run("(range 0 3 0)"),
+            Err("EvaluationError: range step cannot be zero".to_string())
+        );
+        assert!(run("(range 0 1 2 3)").is_err());
+        assert!(run("(range 0 1 0.0)").is_err());
+        assert!(run("(range \"a\")").is_err());
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(run("(list 1 (+ 1 1) 3)"), Ok(integers(&[1, 2, 3])));
+        assert_eq!(run("(list)"), Ok(numbers(&[])));
+    }
+
+    #[test]
+    fn test_nth() {
+        assert_eq!(run("(nth [1 2 3] 0)"), Ok(Value::Integer(1)));
+        assert_eq!(run("(nth [1 2 3] 2)"), Ok(Value::Integer(3)));
+        assert_eq!(
+            run("(nth [[1] \"a\"] 1)"),
+            Ok(Value::String("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_nth_out_of_bounds() {
+        assert_eq!(
+            run("(nth [1 2 3] 3)"),
+            Err("EvaluationError: nth index 3 is out of bounds".to_string())
+        );
+        assert!(run("(nth [] 0)").is_err());
+    }
+
+    #[test]
+    fn test_nth_invalid_index() {
+        assert_eq!(
+            run("(nth [1 2 3] -1)"),
+            Err("EvaluationError: nth expects a non-negative integer index, got -1".to_string())
+        );
+        assert!(run("(nth [1 2 3] 0.5)").is_err());
+        assert!(run("(nth 1 0)").is_err());
+    }
+
+    #[test]
+    fn test_first_second() {
+        assert_eq!(run("(first [4 5 6])"), Ok(Value::Integer(4)));
+        assert_eq!(run("(second [4 5 6])"), Ok(Value::Integer(5)));
+        assert_eq!(
+            run("(second [4])"),
+            Err("EvaluationError: second index 1 is out of bounds".to_string())
+        );
+        assert!(run("(first [])").is_err());
+    }
+
+    #[test]
+    fn test_append() {
+        assert_eq!(run("(append [1 2] [3 4])"), Ok(integers(&[1, 2, 3, 4])));
+        assert_eq!(
+            run("(append [1] [] [2 3] [4])"),
+            Ok(integers(&[1, 2, 3, 4]))
+        );
+        assert_eq!(run("(append)"), Ok(numbers(&[])));
+        assert_eq!(run("(concat [1] [2])"), Ok(integers(&[1, 2])));
+        assert_eq!(run("(append [[1]] [2])"), run("[[1] 2]"));
+    }
+
+    #[test]
+    fn test_append_non_list() {
+        assert!(run("(append [1] 2)").is_err());
+        assert!(run("(append \"ab\" [1])").is_err());
+    }
+
+    #[test]
+    fn test_reverse() {
+        assert_eq!(run("(reverse [1 2 3])"), Ok(integers(&[3, 2, 1])));
+        assert_eq!(run("(reverse [])"), Ok(numbers(&[])));
+        assert!(run("(reverse 1)").is_err());
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(run("(count [1 [2 3] 4])"), Ok(Value::Integer(3)));
+        assert_eq!(run("(count {\"a\" 1 \"b\" 2})"), Ok(Value::Integer(2)));
+        assert_eq!(run("(count \"héllo\")"), Ok(Value::Integer(5)));
+        assert_eq!(run("(count [])"), Ok(Value::Integer(0)));
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(run("(empty? [])"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(empty? [nil])"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(empty? {})"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(empty? \"\")"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(empty? \"a\")"), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn test_count_scalar() {
+        assert_eq!(
+            run("(count 5)"),
+            Err("EvaluationError: count expects a list, map or string, got integer 5".to_string())
+        );
+        assert!(run("(empty? true)").is_err());
+        assert!(run("(count nil)").is_err());
+    }
+
+    #[test]
+    fn test_contains_list() {
+        assert_eq!(
+            run("(contains? [1 [2 3] \"a\"] [2 3])"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(run("(contains? [1 2] \"1\")"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(contains? [] 1)"), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn test_contains_string() {
+        assert_eq!(
+            run("(contains? \"hello\" \"ell\")"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(run("(contains? \"hello\" \"\")"), Ok(Value::Boolean(true)));
+        assert_eq!(
+            run("(contains? \"hello\" \"le\")"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(run("(contains? \"hello\" \\o)"), Ok(Value::Boolean(true)));
+        assert!(run("(contains? \"hello\" 1)").is_err());
+        assert!(run("(contains? 1 1)").is_err());
+    }
+
+    #[test]
+    fn test_index_of() {
+        assert_eq!(run("(index-of [5 6 5] 5)"), Ok(Value::Integer(0)));
+        assert_eq!(run("(index-of [5 6 5] 6)"), Ok(Value::Integer(1)));
+        assert_eq!(run("(index-of [5 6] 7)"), Ok(Value::Integer(-1)));
+        assert_eq!(run("(index-of \"héllo\" \"llo\")"), Ok(Value::Integer(2)));
+        assert_eq!(run("(index-of \"hello\" \"x\")"), Ok(Value::Integer(-1)));
+    }
+
+    #[test]
+    fn test_map_filter() {
+        assert_eq!(run("(map inc [1 2 3])"), Ok(integers(&[2, 3, 4])));
+        assert_eq!(run("(filter even? [1 2 3 4])"), Ok(integers(&[2, 4])));
+        assert_eq!(run("(map inc [])"), Ok(numbers(&[])));
+        assert_eq!(
+            run("(map inc 1)"),
+            Err("EvaluationError: map expects a collection, got integer 1".to_string())
+        );
+        assert!(run("(filter 1 [1])").is_err());
+    }
+
+    #[test]
+    fn test_map_filter_large_list() {
+        assert_eq!(
+            run("(count (map inc (range 100000)))"),
+            Ok(Value::Integer(100000))
+        );
+        assert_eq!(
+            run("(count (filter even? (range 100000)))"),
+            Ok(Value::Integer(50000))
+        );
+    }
+
+    #[test]
+    fn test_take_drop() {
+        assert_eq!(run("(take 2 [1 2 3])"), Ok(integers(&[1, 2])));
+        assert_eq!(run("(drop 2 [1 2 3])"), Ok(integers(&[3])));
+        assert_eq!(run("(take 0 [1 2 3])"), Ok(numbers(&[])));
+        assert_eq!(run("(def l [1 2]) (drop 1 l) l"), Ok(integers(&[1, 2])));
+    }
+
+    #[test]
+    fn test_take_drop_clamp() {
+        assert_eq!(run("(take 5 [1 2])"), Ok(integers(&[1, 2])));
+        assert_eq!(run("(drop 5 [1 2])"), Ok(numbers(&[])));
+        assert!(run("(take -1 [1 2])").is_err());
+        assert!(run("(drop 1.5 [1 2])").is_err());
+    }
+
+    #[test]
+    fn test_flatten() {
+        assert_eq!(
+            run("(flatten [[1 2] [3 [4]]])"),
+            Ok(integers(&[1, 2, 3, 4]))
+        );
+        assert_eq!(run("(flatten [1 [[[2]]] [] 3])"), Ok(integers(&[1, 2, 3])));
+        assert_eq!(run("(flatten [1 2 3])"), Ok(integers(&[1, 2, 3])));
+        assert_eq!(run("(flatten [])"), Ok(numbers(&[])));
+        assert_eq!(run("(flatten [\"a\" [\"b\"]])"), Ok(strings(&["a", "b"])));
+        assert!(run("(flatten 1)").is_err());
+    }
+
+    #[test]
+    fn test_flatten_depth() {
+        assert_eq!(run("(flatten-depth [[1 2] [3 [4]]] 1)"), run("[1 2 3 [4]]"));
+        assert_eq!(run("(flatten-depth [1 [2 [3 [4]]]] 2)"), run("[1 2 3 [4]]"));
+        assert_eq!(run("(flatten-depth [[1] [2]] 0)"), run("[[1] [2]]"));
+        assert_eq!(run("(flatten-depth [1 2 3] 5)"), Ok(integers(&[1, 2, 3])));
+        assert!(run("(flatten-depth [1] -1)").is_err());
+    }
+
+    #[test]
+    fn test_distinct_dedupe() {
+        assert_eq!(
+            run("(distinct [1 2 2 3 1 2 4])"),
+            Ok(integers(&[1, 2, 3, 4]))
+        );
+        assert_eq!(
+            run("(dedupe [1 2 2 3 1 2 4])"),
+            Ok(integers(&[1, 2, 3, 1, 2, 4]))
+        );
+        assert_eq!(
+            run("(distinct [[1 2] {:a 1} [1 2] {:a 1}])"),
+            run("[[1 2] {:a 1}]")
+        );
+        assert_eq!(run("(dedupe [[1] [1] [2] [1]])"), run("[[1] [2] [1]]"));
+        assert_eq!(
+            run("(equal? (distinct [1 1.0 1 [2] [2.0]]) [1 1.0 [2] [2.0]])"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            run("(equal? (dedupe [1 1.0 1.0 1]) [1 1.0 1])"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(run("(distinct [])"), Ok(integers(&[])));
+        assert_eq!(run("(dedupe [])"), Ok(integers(&[])));
+    }
+
+    #[test]
+    fn test_seq() {
+        assert_eq!(run("(seq [1 2])"), Ok(integers(&[1, 2])));
+        assert_eq!(run("(seq \"hé\")"), run("[\\h \\é]"));
+        assert_eq!(run("(seq {:b 2 :a 1})"), run("[[:a 1] [:b 2]]"));
+        assert_eq!(run("(seq {})"), Ok(numbers(&[])));
+        assert_eq!(run("(seq \"\")"), Ok(numbers(&[])));
+        assert_eq!(run("(seq nil)"), Ok(numbers(&[])));
+        assert_eq!(
+            run("(seq 1)"),
+            Err("EvaluationError: seq expects a collection, got integer 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_map_filter_over_sequences() {
+        assert_eq!(
+            run("(map (fn [c] (str c c)) \"ab\")"),
+            Ok(strings(&["aa", "bb"]))
+        );
+        assert_eq!(run("(map first {:x 1 :y 2})"), run("[:x :y]"));
+        assert_eq!(
+            run("(map (fn [e] (* 10 (second e))) (seq {:x 1 :y 2}))"),
+            run("[10 20]")
+        );
+        assert_eq!(
+            run("(filter (fn [c] (not (= c \\-))) \"a-b\")"),
+            run("[\\a \\b]")
+        );
+        assert_eq!(
+            run("(remove (fn [e] (= (first e) :x)) {:x 1 :y 2})"),
+            run("[[:y 2]]")
+        );
+    }
+
+    #[test]
+    fn test_into_list() {
+        assert_eq!(run("(into [] (seq {:a 1 :b 2}))"), run("[[:a 1] [:b 2]]"));
+        assert_eq!(run("(into [] {:a 1})"), run("[[:a 1]]"));
+        assert_eq!(run("(into [0] [1 2])"), Ok(integers(&[0, 1, 2])));
+        assert_eq!(run("(into [] \"ab\")"), run("[\\a \\b]"));
+        assert_eq!(run("(into [] nil)"), Ok(numbers(&[])));
+    }
+
+    #[test]
+    fn test_into_map() {
+        assert_eq!(run("(into {} [[:a 1] [:b 2]])"), run("{:a 1 :b 2}"));
+        assert_eq!(run("(into {:a 0 :c 3} [[:a 1]])"), run("{:a 1 :c 3}"));
+        assert_eq!(run("(into {} [(cons 1 2) [1 3]])"), run("{1 3}"));
+        assert_eq!(run("(into {} (seq {:x [1]}))"), run("{:x [1]}"));
+        assert_eq!(run("(into {} [])"), run("{}"));
+        assert_eq!(
+            run("(into {} [[:a 1 2]])"),
+            Err(
+                "EvaluationError: into expects [key value] entries for a map, got list [:a 1 2]"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            run("(into {} [:a 1])"),
+            Err(
+                "EvaluationError: into expects [key value] entries for a map, got keyword :a"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            run("(into \"\" [1])"),
+            Err(
+                "EvaluationError: into expects a list or a map to add to, got string \"\""
+                    .to_string()
+            )
+        );
+        assert!(run("(into [] 1)").is_err());
+    }
+
+    #[test]
+    fn test_some() {
+        assert_eq!(
+            run("(some (fn [x] (if (> x 1) (* x 10) nil)) [1 2 3])"),
+            Ok(Value::Integer(20))
+        );
+        assert_eq!(run("(some (fn [x] (> x 5)) [1 2 3])"), Ok(Value::Null));
+        assert_eq!(run("(some (fn [x] true) [])"), Ok(Value::Null));
+        assert_eq!(
+            run("(def seen []) (some (fn [x] (set! seen (append seen [x])) (= x 2)) [1 2 3 4]) seen"),
+            Ok(integers(&[1, 2]))
+        );
+        assert!(run("(some 1 [1])").is_err());
+    }
+
+    #[test]
+    fn test_every() {
+        assert_eq!(
+            run("(every (fn [x] (> x 0)) [1 2 3])"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            run("(every (fn [x] (> x 1)) [1 2 3])"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(run("(every (fn [x] false) [])"), Ok(Value::Boolean(true)));
+        assert_eq!(
+            run("(def seen []) (every (fn [x] (set! seen (append seen [x])) (< x 2)) [1 2 3 4]) seen"),
+            Ok(integers(&[1, 2]))
+        );
+        assert_eq!(
+            run("(every (fn [x] (error \"stop\")) [1])"),
+            Err("EvaluationError: stop".to_string())
+        );
+    }
+
+    #[test]
+    fn test_repeat() {
+        assert_eq!(run("(repeat 3 1)"), Ok(integers(&[1, 1, 1])));
+        assert_eq!(run("(repeat 2 \"a\")"), Ok(strings(&["a", "a"])));
+        assert_eq!(run("(repeat 0 1)"), Ok(numbers(&[])));
+        assert!(run("(repeat -1 1)").is_err());
+    }
+
+    #[test]
+    fn test_cycle() {
+        assert_eq!(run("(cycle 5 [1 2])"), Ok(integers(&[1, 2, 1, 2, 1])));
+        assert_eq!(run("(cycle 2 [1 2 3])"), Ok(integers(&[1, 2])));
+        assert_eq!(run("(cycle 0 [1 2])"), Ok(numbers(&[])));
+        assert_eq!(run("(cycle 0 [])"), Ok(numbers(&[])));
+        assert_eq!(
+            run("(cycle 2 [])"),
+            Err("EvaluationError: cycle expects a non-empty list".to_string())
+        );
+    }
+
+    #[test]
+    fn test_repeat_cycle_size_limit() {
+        let mut interpretator = Interpretator::new(None).with_max_collection_size(10);
+        let mut run = |source: &str| {
+            interpretator
+                .run(source.to_owned())
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(run("(count (repeat 10 0))"), Ok(Value::Integer(10)));
+        assert_eq!(run("(count (cycle 10 [1 2 3]))"), Ok(Value::Integer(10)));
+        let limit = Err("EvaluationError: collection size limit exceeded".to_string());
+        assert_eq!(run("(repeat 1000000000000 0)"), limit);
+        assert_eq!(run("(cycle 11 [1 2])"), limit);
+    }
+
+    #[test]
+    fn test_slice() {
+        assert_eq!(run("(slice [1 2 3 4] 1 3)"), Ok(integers(&[2, 3])));
+        assert_eq!(run("(slice [1 2 3] 0 3)"), Ok(integers(&[1, 2, 3])));
+        assert_eq!(run("(slice [1 2 3] 2 2)"), Ok(numbers(&[])));
+    }
+
+    #[test]
+    fn test_slice_out_of_range() {
+        assert_eq!(
+            run("(slice [1 2 3] 1 4)"),
+            Err("EvaluationError: slice range 1..4 is out of bounds for length 3".to_string())
+        );
+        assert!(run("(slice [1 2 3] 2 1)").is_err());
+        assert!(run("(slice [1 2 3] -1 1)").is_err());
+    }
+
+    #[test]
+    fn test_reduce() {
+        assert_eq!(run("(reduce + 0 [1 2 3])"), Ok(Value::Integer(6)));
+        assert_eq!(run("(reduce - 0 [1 2 3])"), Ok(Value::Integer(-6)));
+        assert_eq!(run("(reduce + 5 [])"), Ok(Value::Integer(5)));
+        assert!(run("(reduce 1 0 [1])").is_err());
+    }
+
+    #[test]
+    fn test_fold_right() {
+        assert_eq!(run("(fold-right - 0 [1 2 3])"), Ok(Value::Integer(2)));
+        assert_eq!(
+            run("(fold-right (fn [x acc] (append [x] acc)) [] [1 2 3])"),
+            Ok(integers(&[1, 2, 3]))
+        );
+        assert_eq!(run("(fold-right + 5 [])"), Ok(Value::Integer(5)));
+    }
+
+    #[test]
+    fn test_scan() {
+        assert_eq!(run("(scan + 0 [1 2 3])"), Ok(integers(&[0, 1, 3, 6])));
+        assert_eq!(
+            run("(scan str \"\" [\"a\" \"b\"])"),
+            Ok(strings(&["", "a", "ab"]))
+        );
+        assert_eq!(run("(scan + 0 [])"), Ok(integers(&[0])));
+    }
+
+    #[test]
+    fn test_fractional_and_negative_indices() {
+        assert_eq!(
+            run("(take 1.5 [1 2 3])"),
+            Err("EvaluationError: take expects a non-negative integer index, got 1.5".to_string())
+        );
+        assert_eq!(
+            run("(slice [1 2 3] -1 2)"),
+            Err("EvaluationError: slice expects a non-negative integer index, got -1".to_string())
+        );
+        assert!(run("(nth [1 2 3] 1.5)").is_err());
+        assert!(run("(drop -2 [1 2 3])").is_err());
+        assert!(run("(nth [1 2 3] \"1\")").is_err());
+    }
+
+    #[test]
+    fn test_derived_lists_do_not_alias() {
+        assert_eq!(
+            run("(def xs [1 [2 3]]) (def ys (cdr xs)) (set! ys (cons 0 ys)) xs"),
+            run("[1 [2 3]]")
+        );
+        assert_eq!(
+            run("(def xs [1 2 3]) (def ys (take 2 xs)) (set! ys (append ys [9])) [xs ys]"),
+            run("[[1 2 3] [1 2 9]]")
+        );
+        assert_eq!(
+            run("(def m {:a [1]}) (def n (assoc m :b 2)) (set! n (assoc n :a [])) (get m :a)"),
+            run("[1]")
+        );
+    }
+}
@@ -0,0 +1,486 @@
+use super::super::interpretator::{Context, Scope};
+use super::super::nodes::*;
+use super::{define, function_arg, list_arg, list_value};
+use std::collections::HashMap;
+
+pub fn register(scope: &Scope) {
+    define(scope, "get", &["map", "key"], get);
+    define(scope, "assoc", &["map", "key", "value"], assoc);
+    define(scope, "get-in", &["map", "path"], get_in);
+    define(scope, "assoc-in", &["map", "path", "value"], assoc_in);
+    define(scope, "update", &["map", "key", "f"], update);
+    define(scope, "update-in", &["map", "path", "f"], update_in);
+    define(scope, "keys", &["map"], keys);
+    define(scope, "vals", &["map"], vals);
+    define(scope, "list->map", &["list"], list_to_map);
+    define(scope, "map->list", &["map"], map_to_list);
+    define(scope, "group-by", &["f", "list"], group_by);
+    define(scope, "frequencies", &["list"], frequencies);
+}
+
+fn map_arg<'v>(name: &str, value: &'v Value) -> Result<&'v HashMap<MapKey, Value>, String> {
+    match value {
+        Value::Map(m) => Ok(m),
+        v => Err(format!(
+            "{} expects a map, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+/// Entries sorted by key, so `keys` and `vals` have a stable order.
+fn sorted_entries(map: &HashMap<MapKey, Value>) -> Vec<(&MapKey, &Value)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(k, _)| *k);
+    entries
+}
+
+/// `(get map key)` returns the value stored under `key`, or null.
+fn get(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let map = map_arg("get", &args[0])?;
+    Ok(map
+        .get(&args[1].to_map_key()?)
+        .cloned()
+        .unwrap_or(Value::Null))
+}
+
+/// `(assoc map key value)` returns a copy of `map` with `key` set to `value`.
+fn assoc(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let mut map = map_arg("assoc", &args[0])?.clone();
+    map.insert(args[1].to_map_key()?, args[2].clone());
+    ctx.check_collection_size(map.len())?;
+    Ok(Value::Map(map))
+}
+
+/// `(get-in m [k1 k2...])` follows the keys through nested maps and
+/// returns the value at the end, or null if a key is missing.
+fn get_in(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut value = args[0].clone();
+    for key in list_arg("get-in", &args[1])? {
+        value = match value {
+            Value::Null => return Ok(Value::Null),
+            v => map_arg("get-in", &v)?
+                .get(&key.to_map_key()?)
+                .cloned()
+                .unwrap_or(Value::Null),
+        };
+    }
+    Ok(value)
+}
+
+/// `(assoc-in m [k1 k2...] value)` returns a copy of `m` with `value` set
+/// at the end of the path, creating maps for missing keys on the way.
+fn assoc_in(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let path = list_arg("assoc-in", &args[1])?;
+    if path.is_empty() {
+        return Err("assoc-in expects a non-empty path".to_string());
+    }
+    let value = &args[2];
+    update_path(
+        "assoc-in",
+        &args[0],
+        &path,
+        &mut |_, _| Ok(value.clone()),
+        ctx,
+    )
+}
+
+/// `(update m key f)` returns a copy of `m` with the value under `key`
+/// replaced by `(f value)`. A missing value is passed to `f` as null.
+fn update(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    map_arg("update", &args[0])?;
+    let f = function_arg("update", &args[2])?;
+    update_path(
+        "update",
+        &args[0],
+        &args[1..2],
+        &mut |value, ctx| f.call("update", vec![value], scope, ctx),
+        ctx,
+    )
+}
+
+/// `(update-in m [k1 k2...] f)` is `update` at the end of a path of keys,
+/// creating maps for missing keys on the way like `assoc-in`.
+fn update_in(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let path = list_arg("update-in", &args[1])?;
+    let f = function_arg("update-in", &args[2])?;
+    update_path(
+        "update-in",
+        &args[0],
+        &path,
+        &mut |value, ctx| f.call("update-in", vec![value], scope, ctx),
+        ctx,
+    )
+}
+
+/// Copies the maps along `path`, replacing the value at its end with the
+/// result of `leaf`. Missing maps on the way are created empty.
+fn update_path(
+    name: &str,
+    value: &Value,
+    path: &[Value],
+    leaf: &mut dyn FnMut(Value, &mut Context) -> Result<Value, String>,
+    ctx: &mut Context,
+) -> Result<Value, String> {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return leaf(value.clone(), ctx),
+    };
+    let mut map = match value {
+        Value::Null => HashMap::new(),
+        v => map_arg(name, v)?.clone(),
+    };
+    let key = key.to_map_key()?;
+    let child = map.get(&key).cloned().unwrap_or(Value::Null);
+    map.insert(key, update_path(name, &child, rest, leaf, ctx)?);
+    ctx.check_collection_size(map.len())?;
+    Ok(Value::Map(map))
+}
+
+/// `(keys map)` returns the keys of `map` in sorted order.
+fn keys(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let map = map_arg("keys", &args[0])?;
+    Ok(list_value(
+        sorted_entries(map)
+            .into_iter()
+            .map(|(k, _)| k.to_value())
+            .collect(),
+    ))
+}
+
+/// `(vals map)` returns the values of `map`, ordered by their keys.
+fn vals(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let map = map_arg("vals", &args[0])?;
+    Ok(list_value(
+        sorted_entries(map)
+            .into_iter()
+            .map(|(_, v)| v.clone())
+            .collect(),
+    ))
+}
+
+/// `(list->map [k1 v1 k2 v2])` builds a map from alternating keys and
+/// values. Later entries win over earlier ones with the same key.
+fn list_to_map(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let values = list_arg("list->map", &args[0])?;
+    if values.len() % 2 != 0 {
+        return Err(format!(
+            "list->map expects an even number of elements, got {}",
+            values.len()
+        ));
+    }
+    let mut map = HashMap::new();
+    for pair in values.chunks(2) {
+        map.insert(pair[0].to_map_key()?, pair[1].clone());
+    }
+    ctx.check_collection_size(map.len())?;
+    Ok(Value::Map(map))
+}
+
+/// `(map->list m)` returns the entries of `m` as alternating keys and
+/// values, ordered by key like `keys`.
+fn map_to_list(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let map = map_arg("map->list", &args[0])?;
+    ctx.check_collection_size(map.len() * 2)?;
+    Ok(list_value(
+        sorted_entries(map)
+            .into_iter()
+            .flat_map(|(k, v)| vec![k.to_value(), v.clone()])
+            .collect(),
+    ))
+}
+
+/// `(group-by f list)` returns a map from each result of `f` to the list
+/// of elements, in order, for which `f` returned it.
+fn group_by(args: Vec<Value>, scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let f = function_arg("group-by", &args[0])?;
+    let mut groups: HashMap<MapKey, Vec<Value>> = HashMap::new();
+    for value in list_arg("group-by", &args[1])? {
+        let key = f
+            .call("group-by", vec![value.clone()], scope, ctx)?
+            .to_map_key()?;
+        groups.entry(key).or_default().push(value);
+        ctx.check_collection_size(groups.len())?;
+    }
+    Ok(Value::Map(
+        groups
+            .into_iter()
+            .map(|(key, values)| (key, list_value(values)))
+            .collect(),
+    ))
+}
+
+/// `(frequencies list)` returns a map from each distinct element to the
+/// number of times it occurs.
+fn frequencies(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let mut counts: HashMap<MapKey, i64> = HashMap::new();
+    for value in list_arg("frequencies", &args[0])? {
+        *counts.entry(value.to_map_key()?).or_default() += 1;
+        ctx.check_collection_size(counts.len())?;
+    }
+    Ok(Value::Map(
+        counts
+            .into_iter()
+            .map(|(key, n)| (key, Value::Integer(n)))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_map_literal() {
+        let mut expected = HashMap::new();
+        expected.insert(MapKey::String("a".to_string()), Value::Integer(1));
+        expected.insert(MapKey::Integer(2), string("x"));
+        assert_eq!(
+            run("(def k \"a\") {k 1 2 (str \"x\")}"),
+            Ok(Value::Map(expected))
+        );
+        assert!(run("{[1] 1}").is_err());
+    }
+
+    #[test]
+    fn test_get() {
+        assert!(matches!(
+            run("(get {\"a\" 1 \"b\" 2} \"b\")"),
+            Ok(Value::Integer(2))
+        ));
+        assert_eq!(run("(get {1 \"one\"} 1)"), Ok(string("one")));
+    }
+
+    #[test]
+    fn test_get_missing() {
+        assert_eq!(run("(get {\"a\" 1} \"z\")"), Ok(Value::Null));
+        assert!(run("(get [1] 0)").is_err());
+    }
+
+    #[test]
+    fn test_assoc() {
+        assert!(matches!(
+            run("(get (assoc {\"a\" 1} \"b\" 2) \"b\")"),
+            Ok(Value::Integer(2))
+        ));
+    }
+
+    #[test]
+    fn test_assoc_overwrite() {
+        assert_eq!(
+            run("(def m {\"a\" 1}) (def n (assoc m \"a\" 5)) [(get m \"a\") (get n \"a\")]"),
+            Ok(list_value(vec![Value::Integer(1), Value::Integer(5)]))
+        );
+    }
+
+    #[test]
+    fn test_get_in() {
+        let config = "(def config {:db {:host \"localhost\" :ports [5432]} :debug true})";
+        assert_eq!(
+            run(&format!("{} (get-in config [:db :host])", config)),
+            Ok(string("localhost"))
+        );
+        assert_eq!(
+            run(&format!("{} (get-in config [:db :ports])", config)),
+            run("[5432]")
+        );
+        assert_eq!(
+            run(&format!("{} (get-in config [])", config)),
+            run(&format!("{} config", config))
+        );
+    }
+
+    #[test]
+    fn test_get_in_missing() {
+        assert_eq!(run("(get-in {:a {:b 1}} [:a :c])"), Ok(Value::Null));
+        assert_eq!(run("(get-in {:a {:b 1}} [:x :y :z])"), Ok(Value::Null));
+        assert_eq!(
+            run("(get-in {:a 1} [:a :b])"),
+            Err("EvaluationError: get-in expects a map, got integer 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assoc_in() {
+        assert_eq!(
+            run("(assoc-in {:a {:b 1 :c 2}} [:a :b] 5)"),
+            run("{:a {:b 5 :c 2}}")
+        );
+        assert_eq!(run("(assoc-in {} [:a :b :c] 1)"), run("{:a {:b {:c 1}}}"));
+        assert_eq!(
+            run("(def m {:a {}}) (def n (assoc-in m [:a :b] 1)) [m n]"),
+            run("[{:a {}} {:a {:b 1}}]")
+        );
+    }
+
+    #[test]
+    fn test_assoc_in_invalid() {
+        assert_eq!(
+            run("(assoc-in {:a [1]} [:a :b] 1)"),
+            Err("EvaluationError: assoc-in expects a map, got list [1]".to_string())
+        );
+        assert!(run("(assoc-in {} [] 1)").is_err());
+        assert!(run("(assoc-in {} [[1]] 1)").is_err());
+    }
+
+    #[test]
+    fn test_update() {
+        assert_eq!(
+            run("(def counts {:hits 1}) (def counts (update counts :hits inc)) (update counts :hits inc)"),
+            run("{:hits 3}")
+        );
+        assert_eq!(
+            run("(update {} :n (fn [n] (if (nil? n) 1 (+ n 1))))"),
+            run("{:n 1}")
+        );
+        assert_eq!(run("(def m {:a 1}) (update m :a inc) m"), run("{:a 1}"));
+        assert!(run("(update [1] 0 inc)").is_err());
+        assert!(run("(update {:a 1} :a 5)").is_err());
+        assert!(run("(update {:a \"x\"} :a inc)").is_err());
+    }
+
+    #[test]
+    fn test_update_in() {
+        assert_eq!(
+            run("(update-in {:stats {:hits 41}} [:stats :hits] inc)"),
+            run("{:stats {:hits 42}}")
+        );
+        assert_eq!(
+            run("(update-in {} [:a :b] (fn [x] [x]))"),
+            run("{:a {:b [nil]}}")
+        );
+        assert_eq!(
+            run("(update-in {:a 1} [:a :b] inc)"),
+            Err("EvaluationError: update-in expects a map, got integer 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_keys_and_vals_order() {
+        assert_eq!(
+            run("(keys {\"c\" 3 \"a\" 1 \"b\" 2})"),
+            Ok(list_value(vec![string("a"), string("b"), string("c")]))
+        );
+        assert_eq!(
+            run("(vals {\"c\" 3 \"a\" 1 \"b\" 2})"),
+            Ok(list_value(vec![
+                Value::Integer(1),
+                Value::Integer(2),
+                Value::Integer(3)
+            ]))
+        );
+        assert_eq!(run("(keys {})"), Ok(list_value(vec![])));
+    }
+
+    #[test]
+    fn test_number_keys() {
+        assert_eq!(run("(get {1 \"one\" 2 \"two\"} 2)"), Ok(string("two")));
+        assert_eq!(run("(get {1 \"one\"} \"1\")"), Ok(Value::Null));
+        assert_eq!(run("(get {0 \"zero\"} -0)"), Ok(string("zero")));
+        assert_eq!(
+            run("(get (assoc {} 1.5 true) 1.5)"),
+            Ok(Value::Boolean(true))
+        );
+        assert!(run("(assoc {} (/ 0 0) 1)").is_err());
+    }
+
+    #[test]
+    fn test_keyword_keys() {
+        assert!(matches!(run("(get {:a 1 :b 2} :b)"), Ok(Value::Integer(2))));
+        assert_eq!(run("(get {:a 1} \"a\")"), Ok(Value::Null));
+        assert_eq!(
+            run("(keys {:b 1 \"a\" 2 3 4})"),
+            Ok(list_value(vec![
+                Value::Integer(3),
+                string("a"),
+                Value::Keyword("b".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_unhashable_keys() {
+        assert_eq!(
+            run("{[1] 1}"),
+            Err("EvaluationError: [1] cannot be used as a map key".to_string())
+        );
+        assert!(run("(assoc {} {} 1)").is_err());
+        assert!(run("(get {} +)").is_err());
+        assert!(run("(assoc {} nil 1)").is_err());
+    }
+
+    #[test]
+    fn test_list_to_map() {
+        assert_eq!(run("(list->map [:a 1 \"b\" 2])"), run("{:a 1 \"b\" 2}"));
+        assert_eq!(run("(list->map [1 :x 1 :y])"), run("{1 :y}"));
+        assert_eq!(run("(list->map [])"), Ok(Value::Map(HashMap::new())));
+        assert!(run("(list->map [[1] 2])").is_err());
+    }
+
+    #[test]
+    fn test_list_to_map_odd_length() {
+        assert_eq!(
+            run("(list->map [:a 1 :b])"),
+            Err("EvaluationError: list->map expects an even number of elements, got 3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_map_to_list() {
+        assert_eq!(
+            run("(map->list {:b 2 :a 1})"),
+            Ok(list_value(vec![
+                Value::Keyword("a".to_string()),
+                Value::Integer(1),
+                Value::Keyword("b".to_string()),
+                Value::Integer(2),
+            ]))
+        );
+        assert_eq!(
+            run("(def m {1 \"x\" :k [2]}) (equal? m (list->map (map->list m)))"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            run("(map->list (list->map [2 :b 1 :a]))"),
+            run("[1 :a 2 :b]")
+        );
+    }
+
+    #[test]
+    fn test_group_by() {
+        assert_eq!(
+            run("(group-by (fn [n] (= (mod n 2) 0)) [1 2 3 4 5])"),
+            run("{true [2 4] false [1 3 5]}")
+        );
+        assert_eq!(
+            run("(group-by count [\"a\" \"bb\" \"c\"])"),
+            run("{1 [\"a\" \"c\"] 2 [\"bb\"]}")
+        );
+        assert_eq!(run("(group-by count [])"), Ok(Value::Map(HashMap::new())));
+        assert_eq!(
+            run("(group-by (fn [x] x) [[1]])"),
+            Err("EvaluationError: [1] cannot be used as a map key".to_string())
+        );
+    }
+
+    #[test]
+    fn test_frequencies() {
+        assert_eq!(
+            run("(frequencies (chars \"banana\"))"),
+            run("{\\b 1 \\a 3 \\n 2}")
+        );
+        assert_eq!(
+            run("(frequencies [\"x\" \"y\" \"x\"])"),
+            run("{\"x\" 2 \"y\" 1}")
+        );
+        assert_eq!(run("(frequencies [1 1.0 2])"), run("{1 2 2 1}"));
+        assert_eq!(run("(frequencies [])"), Ok(Value::Map(HashMap::new())));
+    }
+}
@@ -0,0 +1,591 @@
+use super::super::interpretator::{Cast, Context, Scope};
+use super::super::nodes::*;
+use super::{define, list_arg};
+use std::convert::TryFrom;
+
+pub fn register(scope: &Scope) {
+    define(scope, "+", &["&", "numbers"], add);
+    define(scope, "-", &["x", "&", "numbers"], subtract);
+    define(scope, "*", &["&", "numbers"], multiply);
+    define(scope, "/", &["x", "&", "numbers"], divide);
+    define(scope, "inc", &["x"], inc);
+    define(scope, "dec", &["x"], dec);
+    define(scope, "sqrt", &["x"], sqrt);
+    define(scope, "pow", &["base", "exponent"], pow);
+    define(scope, "abs", &["x"], abs);
+    define(scope, "floor", &["x"], floor);
+    define(scope, "ceil", &["x"], ceil);
+    define(scope, "round", &["x"], round);
+    define(scope, "mod", &["x", "divisor"], modulo);
+    define(scope, "%", &["x", "divisor"], modulo);
+    define(scope, "min", &["&", "numbers"], min);
+    define(scope, "max", &["&", "numbers"], max);
+    define(scope, "sum", &["&", "numbers"], sum);
+    define(scope, "zero?", &["x"], is_zero);
+    define(scope, "pos?", &["x"], is_pos);
+    define(scope, "neg?", &["x"], is_neg);
+    define(scope, "even?", &["x"], is_even);
+    define(scope, "odd?", &["x"], is_odd);
+}
+
+/// A number argument. Operations on integers give integers, as long as the
+/// exact result is one, and anything involving a float gives a float.
+#[derive(Clone, Copy)]
+enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+impl Num {
+    fn to_f64(self) -> f64 {
+        match self {
+            Num::Int(i) => i as f64,
+            Num::Float(n) => n,
+        }
+    }
+
+    fn value(self) -> Value {
+        match self {
+            Num::Int(i) => Value::Integer(i),
+            Num::Float(n) => Value::Number(n),
+        }
+    }
+
+    /// Applies `int` when both are integers, failing if it overflows, and
+    /// `float` otherwise.
+    fn combine(
+        self,
+        other: Num,
+        int: fn(i64, i64) -> Option<i64>,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<Num, String> {
+        match (self, other) {
+            (Num::Int(a), Num::Int(b)) => match int(a, b) {
+                Some(i) => Ok(Num::Int(i)),
+                None => Err("integer overflow".to_string()),
+            },
+            (a, b) => Ok(Num::Float(float(a.to_f64(), b.to_f64()))),
+        }
+    }
+}
+
+fn numbers(args: &[Value]) -> Result<Vec<Num>, String> {
+    args.iter()
+        .map(|arg| match arg {
+            Value::Integer(i) => Ok(Num::Int(*i)),
+            v => v
+                .cast_to_number()
+                .map(Num::Float)
+                .map_err(|e| e.to_string()),
+        })
+        .collect()
+}
+
+fn fold(
+    numbers: &[Num],
+    init: Num,
+    int: fn(i64, i64) -> Option<i64>,
+    float: fn(f64, f64) -> f64,
+) -> Result<Value, String> {
+    let mut acc = init;
+    for n in numbers {
+        acc = acc.combine(*n, int, float)?;
+    }
+    Ok(acc.value())
+}
+
+fn add(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    fold(&numbers(&args)?, Num::Int(0), i64::checked_add, |a, b| {
+        a + b
+    })
+}
+
+/// `(- x)` negates `x`, `(- x y...)` subtracts the rest from `x`.
+fn subtract(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let numbers = numbers(&args)?;
+    if numbers.len() == 1 {
+        return fold(&numbers, Num::Int(0), i64::checked_sub, |a, b| a - b);
+    }
+    fold(&numbers[1..], numbers[0], i64::checked_sub, |a, b| a - b)
+}
+
+fn multiply(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    fold(&numbers(&args)?, Num::Int(1), i64::checked_mul, |a, b| {
+        a * b
+    })
+}
+
+/// `(inc x)` is `(+ x 1)`.
+fn inc(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    numbers(&args)?[0]
+        .combine(Num::Int(1), i64::checked_add, |a, b| a + b)
+        .map(Num::value)
+}
+
+/// `(dec x)` is `(- x 1)`.
+fn dec(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    numbers(&args)?[0]
+        .combine(Num::Int(1), i64::checked_sub, |a, b| a - b)
+        .map(Num::value)
+}
+
+/// Integer division that only succeeds when it is exact.
+fn exact_div(a: i64, b: i64) -> Option<i64> {
+    match a.checked_rem(b) {
+        Some(0) => a.checked_div(b),
+        _ => None,
+    }
+}
+
+/// `(/ x)` is `1 / x`, `(/ x y...)` divides `x` by the rest. Dividing
+/// integers gives an integer when the division is exact.
+fn divide(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut numbers = numbers(&args)?;
+    if numbers.len() == 1 {
+        numbers.insert(0, Num::Int(1));
+    }
+    let mut result = numbers[0];
+    for n in &numbers[1..] {
+        if n.to_f64() == 0.0 {
+            return Err("division by zero".to_string());
+        }
+        result = match (result, *n) {
+            (Num::Int(a), Num::Int(b)) => match exact_div(a, b) {
+                Some(i) => Num::Int(i),
+                None => Num::Float(a as f64 / b as f64),
+            },
+            (a, b) => Num::Float(a.to_f64() / b.to_f64()),
+        };
+    }
+    Ok(result.value())
+}
+
+fn sqrt(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let x = numbers(&args)?[0].to_f64();
+    if x < 0.0 {
+        return Err(format!("sqrt of negative number {}", args[0]));
+    }
+    Ok(Value::Number(x.sqrt()))
+}
+
+/// `(pow base exponent)` is an integer for an integer base and a
+/// non-negative integer exponent.
+fn pow(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let numbers = numbers(&args)?;
+    match (numbers[0], numbers[1]) {
+        (Num::Int(base), Num::Int(exponent)) if exponent >= 0 => {
+            match u32::try_from(exponent)
+                .ok()
+                .and_then(|e| base.checked_pow(e))
+            {
+                Some(i) => Ok(Value::Integer(i)),
+                None => Err("integer overflow".to_string()),
+            }
+        }
+        (base, exponent) => Ok(Value::Number(base.to_f64().powf(exponent.to_f64()))),
+    }
+}
+
+fn abs(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    match numbers(&args)?[0] {
+        Num::Int(i) => i
+            .checked_abs()
+            .map(Value::Integer)
+            .ok_or_else(|| "integer overflow".to_string()),
+        Num::Float(n) => Ok(Value::Number(n.abs())),
+    }
+}
+
+/// Applies a rounding function to floats. Integers are already whole.
+fn whole(args: &[Value], round: fn(f64) -> f64) -> Result<Value, String> {
+    match numbers(args)?[0] {
+        Num::Int(i) => Ok(Value::Integer(i)),
+        Num::Float(n) => Ok(Value::Number(round(n))),
+    }
+}
+
+fn floor(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    whole(&args, f64::floor)
+}
+
+fn ceil(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    whole(&args, f64::ceil)
+}
+
+/// Rounds half-way cases away from zero.
+fn round(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    whole(&args, f64::round)
+}
+
+/// `(mod x divisor)` is the remainder of flooring division, so the result
+/// has the sign of the divisor.
+fn modulo(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let numbers = numbers(&args)?;
+    let (x, divisor) = (numbers[0], numbers[1]);
+    if divisor.to_f64() == 0.0 {
+        return Err("division by zero".to_string());
+    }
+    x.combine(
+        divisor,
+        |x, divisor| {
+            let r = x.checked_rem(divisor)?;
+            Some(if r != 0 && (r < 0) != (divisor < 0) {
+                r + divisor
+            } else {
+                r
+            })
+        },
+        |x, divisor| x - divisor * (x / divisor).floor(),
+    )
+    .map(Num::value)
+}
+
+/// The numbers of a reduction, given either as the arguments themselves or
+/// as a single list argument.
+fn reduction_numbers(name: &str, args: &[Value]) -> Result<Vec<Num>, String> {
+    match args {
+        [list @ Value::List(_)] => numbers(&list_arg(name, list)?),
+        args => numbers(args),
+    }
+}
+
+/// The first of `numbers` that `pick` prefers over all the others.
+fn extreme(name: &str, numbers: Vec<Num>, pick: fn(f64, f64) -> bool) -> Result<Value, String> {
+    match numbers
+        .into_iter()
+        .reduce(|a, b| if pick(b.to_f64(), a.to_f64()) { b } else { a })
+    {
+        Some(n) => Ok(n.value()),
+        None => Err(format!("{} expects at least one number", name)),
+    }
+}
+
+fn min(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    extreme("min", reduction_numbers("min", &args)?, |a, b| a < b)
+}
+
+fn max(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    extreme("max", reduction_numbers("max", &args)?, |a, b| a > b)
+}
+
+/// `(sum numbers...)` or `(sum list)`. The sum of no numbers is 0, as
+/// with `+`.
+fn sum(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    fold(
+        &reduction_numbers("sum", &args)?,
+        Num::Int(0),
+        i64::checked_add,
+        |a, b| a + b,
+    )
+}
+
+/// The number passed to the predicate `name`. Unlike arithmetic,
+/// predicates do not cast strings or bools.
+fn number_arg(name: &str, value: &Value) -> Result<f64, String> {
+    value.as_number().ok_or_else(|| {
+        format!(
+            "{} expects a number, got {} {}",
+            name,
+            value.type_name(),
+            value
+        )
+    })
+}
+
+/// Whether the whole number passed to the predicate `name` is even.
+fn even(name: &str, value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Integer(i) => Ok(i % 2 == 0),
+        Value::Number(n) if n.fract() == 0.0 => Ok(n % 2.0 == 0.0),
+        v => Err(format!(
+            "{} expects an integer, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+fn is_zero(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(number_arg("zero?", &args[0])? == 0.0))
+}
+
+fn is_pos(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(number_arg("pos?", &args[0])? > 0.0))
+}
+
+fn is_neg(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(number_arg("neg?", &args[0])? < 0.0))
+}
+
+fn is_even(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(even("even?", &args[0])?))
+}
+
+fn is_odd(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(!even("odd?", &args[0])?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        assert_eq!(run("(+ 1 2 3.5)"), Ok(Value::Number(6.5)));
+        assert_eq!(run("(+)"), Ok(Value::Integer(0)));
+        assert_eq!(run("(+ \"1\" 2)"), Ok(Value::Number(3.0)));
+        assert!(run("(+ 1 [2])").is_err());
+    }
+
+    #[test]
+    fn test_subtract() {
+        let scope = Scope::new(None);
+        let mut ctx = Context::new();
+        assert_eq!(
+            subtract(
+                vec![Value::Integer(10), Value::Integer(3), Value::Integer(2)],
+                &scope,
+                &mut ctx
+            ),
+            Ok(Value::Integer(5))
+        );
+        assert_eq!(
+            subtract(vec![Value::Integer(4)], &scope, &mut ctx),
+            Ok(Value::Integer(-4))
+        );
+    }
+
+    #[test]
+    fn test_subtract_source() {
+        assert_eq!(run("(- 5 3)"), Ok(Value::Integer(2)));
+        assert_eq!(run("(- 5)"), Ok(Value::Integer(-5)));
+        assert_eq!(run("(- 5 -3)"), Ok(Value::Integer(8)));
+    }
+
+    #[test]
+    fn test_multiply() {
+        assert_eq!(run("(* 2 3 4)"), Ok(Value::Integer(24)));
+        assert_eq!(run("(*)"), Ok(Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_divide() {
+        assert_eq!(run("(/ 12 3 2)"), Ok(Value::Integer(2)));
+        assert_eq!(run("(/ 4)"), Ok(Value::Number(0.25)));
+        assert_eq!(
+            run("(/ 1 0)"),
+            Err("EvaluationError: division by zero".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sqrt() {
+        assert_eq!(run("(sqrt 16)"), Ok(Value::Number(4.0)));
+        assert_eq!(run("(sqrt 0)"), Ok(Value::Number(0.0)));
+        assert_eq!(
+            run("(sqrt -4)"),
+            Err("EvaluationError: sqrt of negative number -4".to_string())
+        );
+    }
+
+    #[test]
+    fn test_pow() {
+        assert_eq!(run("(pow 2 10)"), Ok(Value::Integer(1024)));
+        assert_eq!(run("(pow 4 0.5)"), Ok(Value::Number(2.0)));
+        assert_eq!(run("(pow 2 -1)"), Ok(Value::Number(0.5)));
+        assert!(run("(pow 2)").is_err());
+    }
+
+    #[test]
+    fn test_abs() {
+        assert_eq!(run("(abs -3.5)"), Ok(Value::Number(3.5)));
+        assert_eq!(run("(abs 2)"), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_floor_ceil_round() {
+        assert_eq!(run("(floor 2.7)"), Ok(Value::Number(2.0)));
+        assert_eq!(run("(floor -2.2)"), Ok(Value::Number(-3.0)));
+        assert_eq!(run("(ceil 2.2)"), Ok(Value::Number(3.0)));
+        assert_eq!(run("(ceil -2.7)"), Ok(Value::Number(-2.0)));
+        assert_eq!(run("(round 2.5)"), Ok(Value::Number(3.0)));
+        assert_eq!(run("(round -2.5)"), Ok(Value::Number(-3.0)));
+        assert_eq!(run("(round 2.4)"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn test_mod() {
+        assert_eq!(run("(mod 7 3)"), Ok(Value::Integer(1)));
+        assert_eq!(run("(mod -7 3)"), Ok(Value::Integer(2)));
+        assert_eq!(run("(mod 7 -3)"), Ok(Value::Integer(-2)));
+        assert_eq!(run("(% 5.5 2)"), Ok(Value::Number(1.5)));
+        assert_eq!(
+            run("(mod 1 0)"),
+            Err("EvaluationError: division by zero".to_string())
+        );
+    }
+
+    #[test]
+    fn test_min_max() {
+        assert_eq!(run("(min 3 1 2)"), Ok(Value::Integer(1)));
+        assert_eq!(run("(max 3 1 2)"), Ok(Value::Integer(3)));
+        assert_eq!(run("(min -1.5)"), Ok(Value::Number(-1.5)));
+    }
+
+    #[test]
+    fn test_min_max_list() {
+        assert_eq!(run("(min [4 -2 7])"), Ok(Value::Integer(-2)));
+        assert_eq!(run("(max [4 -2 7])"), Ok(Value::Integer(7)));
+        assert!(run("(max [1] [2])").is_err());
+    }
+
+    #[test]
+    fn test_min_max_empty() {
+        assert_eq!(
+            run("(min)"),
+            Err("EvaluationError: min expects at least one number".to_string())
+        );
+        assert_eq!(
+            run("(max [])"),
+            Err("EvaluationError: max expects at least one number".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sum() {
+        assert_eq!(run("(sum [1 2 3])"), Ok(Value::Integer(6)));
+        assert_eq!(run("(sum 1 2 3.5)"), Ok(Value::Number(6.5)));
+        assert_eq!(run("(sum [])"), Ok(Value::Integer(0)));
+        assert!(run("(sum [1 \"a\"])").is_err());
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        assert_eq!(run("(+ 1 2)").map(|v| v.to_string()), Ok("3".to_string()));
+        assert_eq!(
+            run("(+ 1 2.0)").map(|v| v.to_string()),
+            Ok("3.0".to_string())
+        );
+        assert!(matches!(run("(* 6 7)"), Ok(Value::Integer(42))));
+        assert!(matches!(run("(- 5)"), Ok(Value::Integer(-5))));
+        assert!(matches!(run("(mod -7 3)"), Ok(Value::Integer(2))));
+        assert!(matches!(run("(pow 2 10)"), Ok(Value::Integer(1024))));
+        assert!(matches!(run("(abs -2)"), Ok(Value::Integer(2))));
+        assert!(matches!(run("(floor 2)"), Ok(Value::Integer(2))));
+        assert!(matches!(run("(floor 2.5)"), Ok(Value::Number(_))));
+        assert!(matches!(run("(max 1 2.5 2)"), Ok(Value::Number(_))));
+        assert!(matches!(run("(max 1 2.0 3)"), Ok(Value::Integer(3))));
+    }
+
+    #[test]
+    fn test_inc_dec() {
+        assert!(matches!(run("(inc 1)"), Ok(Value::Integer(2))));
+        assert!(matches!(run("(dec 0)"), Ok(Value::Integer(-1))));
+        assert_eq!(run("(inc 1.5)"), Ok(Value::Number(2.5)));
+        assert_eq!(run("(dec 1.5)"), Ok(Value::Number(0.5)));
+        assert_eq!(
+            run("(def n 0) (dotimes [i 3] (set! n (inc n))) n"),
+            Ok(Value::Integer(3))
+        );
+    }
+
+    #[test]
+    fn test_inc_dec_invalid() {
+        assert_eq!(
+            run("(inc [1])"),
+            Err("EvaluationError: CastError: Cannot cast list to number".to_string())
+        );
+        assert!(run("(dec nil)").is_err());
+        assert!(run("(inc)").is_err());
+        assert_eq!(
+            run("(inc 9223372036854775807)"),
+            Err("EvaluationError: integer overflow".to_string())
+        );
+    }
+
+    #[test]
+    fn test_integer_division() {
+        assert!(matches!(run("(/ 6 2)"), Ok(Value::Integer(3))));
+        assert_eq!(run("(/ 7 2)"), Ok(Value::Number(3.5)));
+        assert!(matches!(run("(/ 7 2)"), Ok(Value::Number(_))));
+        assert!(matches!(run("(/ 6.0 2)"), Ok(Value::Number(_))));
+    }
+
+    #[test]
+    fn test_integer_precision() {
+        assert!(matches!(
+            run("(+ 9007199254740993 1)"),
+            Ok(Value::Integer(9007199254740994))
+        ));
+        assert!(matches!(
+            run("(* 3037000499 3037000499)"),
+            Ok(Value::Integer(9223372030926249001))
+        ));
+        assert_eq!(
+            run("(* 9223372036854775807 2)"),
+            Err("EvaluationError: integer overflow".to_string())
+        );
+    }
+
+    #[test]
+    fn test_integer_and_float_are_equal_by_value() {
+        assert_eq!(run("(= 3 3.0)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(< 2 2.5 3)"), Ok(Value::Boolean(true)));
+        assert_eq!(
+            run("(= 9007199254740993 9007199254740992.0)"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(
+            run("(< 9007199254740992.0 9007199254740993)"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            run("(> 9223372036854775807 9223372036854775808.0)"),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(Value::Integer(3).to_string(), "3");
+        assert_eq!(Value::Number(3.0).to_string(), "3.0");
+    }
+
+    #[test]
+    fn test_sign_predicates() {
+        assert_eq!(run("(zero? 0)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(zero? -0.0)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(zero? 0.5)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(pos? 3)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(pos? 0)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(pos? -1.5)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(neg? -1.5)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(neg? 0)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(neg? 2)"), Ok(Value::Boolean(false)));
+        assert_eq!(
+            run("(zero? \"0\")"),
+            Err("EvaluationError: zero? expects a number, got string \"0\"".to_string())
+        );
+        assert_eq!(
+            run("(pos? true)"),
+            Err("EvaluationError: pos? expects a number, got bool true".to_string())
+        );
+        assert!(run("(neg? nil)").is_err());
+    }
+
+    #[test]
+    fn test_parity_predicates() {
+        assert_eq!(run("(even? 4)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(even? -3)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(even? 0)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(odd? -3)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(odd? 10)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(odd? 7.0)"), Ok(Value::Boolean(true)));
+        assert_eq!(
+            run("(even? 2.5)"),
+            Err("EvaluationError: even? expects an integer, got float 2.5".to_string())
+        );
+        assert_eq!(
+            run("(odd? \"1\")"),
+            Err("EvaluationError: odd? expects an integer, got string \"1\"".to_string())
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use super::interpretator::{Context, InterpretatorError, Scope};
+use super::nodes::*;
+use std::rc::Rc;
+
+mod bits;
+mod compare;
+mod errors;
+mod functions;
+mod io;
+mod lists;
+mod maps;
+mod math;
+mod pairs;
+mod random;
+mod strings;
+mod symbols;
+mod types;
+
+/// Lisp source for the functions and macros of the standard library that
+/// are written in the language itself, on top of the natives.
+pub const SOURCE: &str = include_str!("prelude.lisp");
+
+/// Registers the standard native functions in `scope`.
+pub fn register(scope: &Scope) {
+    bits::register(scope);
+    compare::register(scope);
+    errors::register(scope);
+    functions::register(scope);
+    io::register(scope);
+    lists::register(scope);
+    maps::register(scope);
+    math::register(scope);
+    pairs::register(scope);
+    random::register(scope);
+    strings::register(scope);
+    symbols::register(scope);
+    types::register(scope);
+}
+
+fn define(
+    scope: &Scope,
+    name: &str,
+    args: &[&str],
+    func: fn(Vec<Value>, &Scope, &mut Context) -> Result<Value, String>,
+) {
+    scope.set(name.to_string(), native(name, args, func));
+}
+
+/// Makes a native function value, which unlike the ones `define` binds can
+/// capture values.
+fn native<F>(name: &str, args: &[&str], func: F) -> Value
+where
+    F: Fn(Vec<Value>, &Scope, &mut Context) -> Result<Value, String> + 'static,
+{
+    Value::Function(Function::Native(NativeFunction {
+        name: name.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        func: Rc::new(func),
+    }))
+}
+
+/// Returns the elements of a list argument. The names and calls in a list
+/// a macro received as an argument are returned as quoted forms.
+fn list_arg(name: &str, value: &Value) -> Result<Vec<Value>, String> {
+    match value {
+        Value::List(nodes) => Ok(nodes
+            .iter()
+            .map(|node| match node {
+                Node::Atom(v) => v.clone(),
+                form => Value::Quoted(Box::new(form.clone())),
+            })
+            .collect()),
+        v => Err(format!(
+            "{} expects a list, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+fn list_value(values: Vec<Value>) -> Value {
+    Value::List(values.into_iter().map(Node::Atom).collect())
+}
+
+/// Returns the function passed as an argument.
+fn function_arg<'v>(name: &str, value: &'v Value) -> Result<&'v Function, String> {
+    match value {
+        Value::Function(f) => Ok(f),
+        v => Err(format!(
+            "{} expects a function, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+/// Converts an index or count to `usize`. Indices never count from the
+/// end, so negative and fractional numbers are rejected like non-numbers.
+fn value_to_index(value: &Value) -> Result<usize, InterpretatorError> {
+    match value {
+        Value::Integer(i) if *i >= 0 => Ok(*i as usize),
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        v => Err(InterpretatorError::CastError(format!(
+            "expects a non-negative integer index, got {}",
+            v
+        ))),
+    }
+}
+
+/// Runs `value_to_index` on an argument of the native `name`.
+pub(crate) fn index_arg(name: &str, value: &Value) -> Result<usize, String> {
+    value_to_index(value).map_err(|e| match e {
+        InterpretatorError::CastError(message) => format!("{} {}", name, message),
+        e => e.to_string(),
+    })
+}
+
+#[cfg(test)]
+fn run(source: &str) -> Result<Value, String> {
+    super::interpretator::Interpretator::new(None)
+        .run(source.to_owned())
+        .map_err(|e| e.to_string())
+}
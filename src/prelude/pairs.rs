@@ -0,0 +1,109 @@
+use super::super::interpretator::{Context, Scope};
+use super::super::nodes::*;
+use super::{define, list_arg, list_value};
+
+pub fn register(scope: &Scope) {
+    define(scope, "cons", &["head", "tail"], cons);
+    define(scope, "car", &["pair"], car);
+    define(scope, "cdr", &["pair"], cdr);
+}
+
+fn atom(name: &str, node: &Node) -> Result<Value, String> {
+    match node {
+        Node::Atom(v) => Ok(v.clone()),
+        other => Err(format!(
+            "{} got an unevaluated pair element {:?}",
+            name, other
+        )),
+    }
+}
+
+/// The head and tail of a pair, or the first element and the rest of a
+/// non-empty list.
+fn split(name: &str, value: &Value) -> Result<(Value, Value), String> {
+    match value {
+        Value::Pair(head, tail) => Ok((atom(name, head)?, atom(name, tail)?)),
+        Value::List(_) => {
+            let mut values = list_arg(name, value)?;
+            if values.is_empty() {
+                return Err(format!("{} of an empty list", name));
+            }
+            let head = values.remove(0);
+            Ok((head, list_value(values)))
+        }
+        v => Err(format!(
+            "{} expects a pair or a list, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+/// `(cons head tail)` makes the pair `(head . tail)`.
+fn cons(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut args = args.into_iter().map(Node::Atom);
+    Ok(Value::Pair(
+        Box::new(args.next().unwrap()),
+        Box::new(args.next().unwrap()),
+    ))
+}
+
+/// `(car pair)` returns the head of a pair or the first element of a list.
+fn car(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(split("car", &args[0])?.0)
+}
+
+/// `(cdr pair)` returns the tail of a pair or all but the first element of
+/// a list.
+fn cdr(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(split("cdr", &args[0])?.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    fn pair(head: Value, tail: Value) -> Value {
+        Value::Pair(Box::new(Node::Atom(head)), Box::new(Node::Atom(tail)))
+    }
+
+    #[test]
+    fn test_cons() {
+        assert_eq!(
+            run("(cons 1 2)"),
+            Ok(pair(Value::Integer(1), Value::Integer(2)))
+        );
+        assert_eq!(run("(cons 1 2)"), run("'(1 . 2)"));
+        assert_eq!(
+            run("(= (cons 1 [2]) (cons 1 [2]))"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            run("(str (cons :a (cons 2 nil)))"),
+            Ok(Value::String("(:a . (2 . nil))".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_car_cdr() {
+        assert_eq!(run("(car '(1 . 2))"), Ok(Value::Integer(1)));
+        assert_eq!(run("(cdr '(1 . 2))"), Ok(Value::Integer(2)));
+        assert_eq!(run("(car (cdr '(1 2 . 3)))"), Ok(Value::Integer(2)));
+        assert_eq!(run("(def x 5) (cdr (:a . x))"), Ok(Value::Integer(5)));
+        assert_eq!(run("(cdr [:a . (+ 1 2)])"), Ok(Value::Integer(3)));
+    }
+
+    #[test]
+    fn test_car_cdr_lists() {
+        assert_eq!(run("(car [1 2 3])"), Ok(Value::Integer(1)));
+        assert_eq!(run("(cdr [1 2 3])"), run("[2 3]"));
+        assert_eq!(run("(cdr [1])"), run("[]"));
+        assert_eq!(
+            run("(car [])"),
+            Err("EvaluationError: car of an empty list".to_string())
+        );
+        assert!(run("(cdr 1)").is_err());
+    }
+}
@@ -0,0 +1,21 @@
+; The part of the standard library written in the language itself. It is
+; evaluated in the global scope of every new Interpretator, after the
+; native functions are registered, so it can only use those and the
+; special forms.
+
+; (when test body...) evaluates the body like do when test is truthy, and
+; returns null otherwise. unless is when with the test negated.
+(defmacro when [test & body]
+  (list 'if test (apply list 'do body) nil))
+
+(defmacro unless [test & body]
+  (list 'if test nil (apply list 'do body)))
+
+(defn not [x] (if x false true))
+
+(defn complement [f]
+  (fn [& args] (not (apply f args))))
+
+; remove takes any collection seq accepts, like filter.
+(defn remove [pred xs]
+  (filter (complement pred) xs))
@@ -0,0 +1,112 @@
+use super::super::interpretator::{seed_rng, Context, Scope};
+use super::super::nodes::*;
+use super::define;
+
+pub fn register(scope: &Scope) {
+    define(scope, "random", &[], random);
+    define(scope, "random-int", &["n"], random_int);
+    define(scope, "seed", &["n"], seed);
+}
+
+/// Advances the xorshift64* generator and returns its next output.
+fn next(ctx: &mut Context) -> u64 {
+    let mut x = ctx.rng_state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    ctx.rng_state = x;
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+/// `(random)` returns a float in `[0, 1)`.
+fn random(_: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    Ok(Value::Number(
+        (next(ctx) >> 11) as f64 / (1u64 << 53) as f64,
+    ))
+}
+
+/// `(random-int n)` returns an integer in `[0, n)`.
+fn random_int(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let n = match &args[0] {
+        Value::Integer(i) if *i > 0 => *i as u64,
+        Value::Number(n) if *n >= 1.0 && n.fract() == 0.0 => *n as u64,
+        v => {
+            return Err(format!(
+                "random-int expects a positive integer bound, got {} {}",
+                v.type_name(),
+                v
+            ))
+        }
+    };
+    Ok(Value::Integer((next(ctx) % n) as i64))
+}
+
+/// `(seed n)` restarts the generator, so the same seed always gives the
+/// same sequence of random numbers.
+fn seed(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let seed = match &args[0] {
+        Value::Integer(i) => *i as u64,
+        Value::Number(n) if n.fract() == 0.0 => *n as i64 as u64,
+        v => {
+            return Err(format!(
+                "seed expects an integer, got {} {}",
+                v.type_name(),
+                v
+            ))
+        }
+    };
+    ctx.rng_state = seed_rng(seed);
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    #[test]
+    fn test_seeded_sequence() {
+        let draws = |seed| {
+            run(&format!(
+                "(seed {}) [(random-int 100) (random-int 100) (random-int 100) (random-int 100)]",
+                seed
+            ))
+        };
+        assert_eq!(draws(42), run("[42 23 59 63]"));
+        assert_eq!(draws(42), draws(42));
+        assert_ne!(draws(42), draws(43));
+        assert_eq!(
+            run("(seed 7) (def a (random)) (seed 7) (= a (random))"),
+            Ok(Value::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn test_random_range() {
+        let source = "(seed 1) (def ok true)
+            (dotimes [i 200]
+              (def x (random))
+              (def n (random-int 7))
+              (when (< x 0) (set! ok false))
+              (when (>= x 1) (set! ok false))
+              (when (< n 0) (set! ok false))
+              (when (>= n 7) (set! ok false)))
+            ok";
+        assert_eq!(run(source), Ok(Value::Boolean(true)));
+        assert!(matches!(run("(random)"), Ok(Value::Number(_))));
+    }
+
+    #[test]
+    fn test_random_invalid_arguments() {
+        assert!(run("(random-int 0)").is_err());
+        assert_eq!(
+            run("(random-int -5)"),
+            Err(
+                "EvaluationError: random-int expects a positive integer bound, got integer -5"
+                    .to_string()
+            )
+        );
+        assert!(run("(random-int 2.5)").is_err());
+        assert!(run("(seed \"a\")").is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use super::super::interpretator::{Cast, Context, Scope};
+use super::super::nodes::*;
+use super::{define, index_arg, list_arg, list_value};
+
+pub fn register(scope: &Scope) {
+    define(scope, "str", &["&", "values"], str);
+    define(scope, "length", &["value"], length);
+    define(scope, "substring", &["string", "start", "end"], substring);
+    define(scope, "split", &["string", "separator"], split);
+    define(scope, "words", &["string"], words);
+    define(scope, "join", &["separator", "list"], join);
+    define(scope, "format", &["template", "&", "values"], format);
+    define(
+        scope,
+        "string-replace",
+        &["string", "from", "to"],
+        string_replace,
+    );
+    define(scope, "trim", &["string"], trim);
+    define(scope, "upper", &["string"], upper);
+    define(scope, "lower", &["string"], lower);
+    define(scope, "char-at", &["string", "index"], char_at);
+    define(scope, "chars", &["string"], chars);
+    define(scope, "string-from-chars", &["chars"], string_from_chars);
+}
+
+fn cast_to_string(value: &Value) -> Result<String, String> {
+    value.cast_to_string().map_err(|e| e.to_string())
+}
+
+/// `(str values...)` concatenates the string casts of its arguments.
+fn str(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut result = String::new();
+    for arg in &args {
+        result.push_str(&cast_to_string(arg)?);
+    }
+    Ok(Value::String(result))
+}
+
+/// `(length value)` counts the characters of a string or the elements of a
+/// list.
+fn length(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    match &args[0] {
+        Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
+        Value::List(l) => Ok(Value::Integer(l.len() as i64)),
+        v => Err(format!(
+            "length expects a string or a list, got {} {}",
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+/// `(substring s start end)` takes the characters in `start..end`.
+fn substring(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let s = cast_to_string(&args[0])?;
+    let start = index_arg("substring", &args[1])?;
+    let end = index_arg("substring", &args[2])?;
+    let len = s.chars().count();
+    if start > end || end > len {
+        return Err(format!(
+            "substring range {}..{} is out of bounds for length {}",
+            start, end, len
+        ));
+    }
+    Ok(Value::String(
+        s.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+/// `(split s separator)` returns the list of pieces between separators.
+fn split(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let s = cast_to_string(&args[0])?;
+    let separator = cast_to_string(&args[1])?;
+    if separator.is_empty() {
+        return Err("split separator must not be empty".to_string());
+    }
+    ctx.check_collection_size(s.matches(separator.as_str()).count() + 1)?;
+    Ok(Value::List(
+        s.split(separator.as_str())
+            .map(|part| Node::Atom(Value::String(part.to_string())))
+            .collect(),
+    ))
+}
+
+/// `(words s)` splits a string on runs of whitespace, ignoring leading and
+/// trailing whitespace.
+fn words(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let s = cast_to_string(&args[0])?;
+    let words: Vec<Value> = s
+        .split_whitespace()
+        .map(|word| Value::String(word.to_string()))
+        .collect();
+    ctx.check_collection_size(words.len())?;
+    Ok(list_value(words))
+}
+
+/// `(join separator lst)` concatenates the string casts of the elements of
+/// `lst` with `separator` between them.
+fn join(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let separator = cast_to_string(&args[0])?;
+    let parts = list_arg("join", &args[1])?
+        .iter()
+        .map(cast_to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::String(parts.join(&separator)))
+}
+
+/// `(format template values...)` replaces each `{}` in `template` with the
+/// string cast of the next value. `{{` and `}}` stand for literal braces.
+fn format(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let template = cast_to_string(&args[0])?;
+    let mut values = args[1..].iter();
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                match values.next() {
+                    Some(value) => result.push_str(&cast_to_string(value)?),
+                    None => {
+                        return Err(format!(
+                            "format template has more placeholders than the {} values given",
+                            args.len() - 1
+                        ))
+                    }
+                }
+            }
+            ('{', _) | ('}', _) => {
+                return Err(format!(
+                    "format template has an unmatched '{}', write '{}{}' for a literal brace",
+                    c, c, c
+                ))
+            }
+            _ => result.push(c),
+        }
+    }
+    if values.next().is_some() {
+        return Err(format!(
+            "format was given {} values but the template uses fewer",
+            args.len() - 1
+        ));
+    }
+    Ok(Value::String(result))
+}
+
+/// `(string-replace s from to)` replaces every occurrence of `from`.
+fn string_replace(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let s = cast_to_string(&args[0])?;
+    let from = cast_to_string(&args[1])?;
+    let to = cast_to_string(&args[2])?;
+    if from.is_empty() {
+        return Err("string-replace pattern must not be empty".to_string());
+    }
+    Ok(Value::String(s.replace(from.as_str(), &to)))
+}
+
+/// `(trim s)` removes leading and trailing whitespace.
+fn trim(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::String(cast_to_string(&args[0])?.trim().to_string()))
+}
+
+fn upper(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::String(cast_to_string(&args[0])?.to_uppercase()))
+}
+
+fn lower(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::String(cast_to_string(&args[0])?.to_lowercase()))
+}
+
+/// `(char-at s i)` returns the character at index `i`.
+fn char_at(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let s = cast_to_string(&args[0])?;
+    let index = index_arg("char-at", &args[1])?;
+    match s.chars().nth(index) {
+        Some(c) => Ok(Value::Char(c)),
+        None => Err(format!(
+            "char-at index {} is out of bounds for length {}",
+            index,
+            s.chars().count()
+        )),
+    }
+}
+
+/// `(chars s)` returns the list of characters of a string.
+fn chars(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let s = cast_to_string(&args[0])?;
+    ctx.check_collection_size(s.chars().count())?;
+    Ok(list_value(s.chars().map(Value::Char).collect()))
+}
+
+/// `(string-from-chars lst)` joins a list of characters into a string.
+fn string_from_chars(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let mut result = String::new();
+    for value in list_arg("string-from-chars", &args[0])? {
+        match value {
+            Value::Char(c) => result.push(c),
+            v => {
+                return Err(format!(
+                    "string-from-chars expects chars, got {} {}",
+                    v.type_name(),
+                    v
+                ))
+            }
+        }
+    }
+    Ok(Value::String(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_str() {
+        assert_eq!(run("(str \"a\" 1 true)"), Ok(string("a1true")));
+        assert_eq!(run("(str)"), Ok(string("")));
+    }
+
+    #[test]
+    fn test_length() {
+        assert!(matches!(run("(length \"hello\")"), Ok(Value::Integer(5))));
+        assert!(matches!(run("(length [1 2 3])"), Ok(Value::Integer(3))));
+        assert!(run("(length 1)").is_err());
+    }
+
+    #[test]
+    fn test_length_counts_chars() {
+        assert!(matches!(
+            run("(length \"héllo wörld\")"),
+            Ok(Value::Integer(11))
+        ));
+        assert!(matches!(run("(length \"日本語\")"), Ok(Value::Integer(3))));
+    }
+
+    #[test]
+    fn test_substring() {
+        assert_eq!(run("(substring \"hello\" 1 3)"), Ok(string("el")));
+        assert_eq!(run("(substring \"日本語\" 1 3)"), Ok(string("本語")));
+        assert_eq!(run("(substring \"hello\" 2 2)"), Ok(string("")));
+    }
+
+    #[test]
+    fn test_substring_out_of_range() {
+        assert_eq!(
+            run("(substring \"hello\" 2 10)"),
+            Err("EvaluationError: substring range 2..10 is out of bounds for length 5".to_string())
+        );
+        assert!(run("(substring \"hello\" 3 1)").is_err());
+        assert!(run("(substring \"hello\" 0.5 1)").is_err());
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            run("(split \"a,b,,c\" \",\")"),
+            Ok(Value::List(vec![
+                Node::Atom(string("a")),
+                Node::Atom(string("b")),
+                Node::Atom(string("")),
+                Node::Atom(string("c")),
+            ]))
+        );
+        assert!(run("(split \"abc\" \"\")").is_err());
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            run("(words \"  the quick\t brown\nfox  \")"),
+            Ok(list_value(vec![
+                string("the"),
+                string("quick"),
+                string("brown"),
+                string("fox"),
+            ]))
+        );
+        assert_eq!(run("(words \"\")"), Ok(list_value(vec![])));
+        assert_eq!(run("(words \"   \")"), Ok(list_value(vec![])));
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!(
+            run("(join \", \" [\"a\" \"b\" \"c\"])"),
+            Ok(string("a, b, c"))
+        );
+        assert_eq!(run("(join \"-\" [1 2.5 :k \\c])"), Ok(string("1-2.5-:k-c")));
+        assert_eq!(run("(join \",\" [])"), Ok(string("")));
+        assert_eq!(run("(join \",\" [\"only\"])"), Ok(string("only")));
+        assert_eq!(run("(join \" \" (words \" a  b \"))"), Ok(string("a b")));
+        assert!(run("(join \",\" \"abc\")").is_err());
+    }
+
+    #[test]
+    fn test_char_at() {
+        assert_eq!(run("(char-at \"hello\" 1)"), Ok(Value::Char('e')));
+        assert_eq!(run("(char-at \"日本語\" 2)"), Ok(Value::Char('語')));
+        assert_eq!(
+            run("(char-at \"abc\" 3)"),
+            Err("EvaluationError: char-at index 3 is out of bounds for length 3".to_string())
+        );
+        assert_eq!(
+            run("(char-at \"abc\" -1)"),
+            Err(
+                "EvaluationError: char-at expects a non-negative integer index, got -1".to_string()
+            )
+        );
+        assert!(run("(char-at \"abc\" 0.5)").is_err());
+    }
+
+    #[test]
+    fn test_chars() {
+        assert_eq!(
+            run("(chars \"hé\")"),
+            Ok(Value::List(vec![
+                Node::Atom(Value::Char('h')),
+                Node::Atom(Value::Char('é')),
+            ]))
+        );
+        assert_eq!(run("(chars \"\")"), Ok(Value::List(vec![])));
+    }
+
+    #[test]
+    fn test_string_from_chars() {
+        assert_eq!(
+            run("(string-from-chars [\\h \\i \\space \\!])"),
+            Ok(string("hi !"))
+        );
+        assert_eq!(
+            run("(string-from-chars (reverse (chars \"abc\")))"),
+            Ok(string("cba"))
+        );
+        assert!(run("(string-from-chars [\"a\"])").is_err());
+    }
+
+    #[test]
+    fn test_chars_in_strings() {
+        assert_eq!(run("(str \\a \"b\" \\c)"), Ok(string("abc")));
+        assert_eq!(run("(= (char-at \"abc\" 0) \\a)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(< \\a \\b)"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_string_replace() {
+        assert_eq!(
+            run("(string-replace \"one fish two fish\" \"fish\" \"cat\")"),
+            Ok(string("one cat two cat"))
+        );
+        assert_eq!(run("(string-replace \"a-b\" \"-\" \"\")"), Ok(string("ab")));
+        assert_eq!(run("(string-replace 1.5 \".\" \",\")"), Ok(string("1,5")));
+        assert!(run("(string-replace \"abc\" \"\" \"x\")").is_err());
+    }
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(run("(trim \"  hi there \t\")"), Ok(string("hi there")));
+        assert_eq!(run("(trim \"\")"), Ok(string("")));
+    }
+
+    #[test]
+    fn test_upper_lower() {
+        assert_eq!(run("(upper \"Héllo\")"), Ok(string("HÉLLO")));
+        assert_eq!(run("(lower \"Héllo\")"), Ok(string("héllo")));
+        assert_eq!(run("(upper true)"), Ok(string("TRUE")));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            run("(format \"{} + {} = {}\" 1 2 3)"),
+            Ok(string("1 + 2 = 3"))
+        );
+        assert_eq!(run("(format \"{}: {}\" \\x [1 2])"), Ok(string("x: [1 2]")));
+        assert_eq!(run("(format \"plain\")"), Ok(string("plain")));
+    }
+
+    #[test]
+    fn test_format_escaped_braces() {
+        assert_eq!(run("(format \"{{{}}} {{}}\" 1)"), Ok(string("{1} {}")));
+        assert!(run("(format \"{ }\" 1)").is_err());
+        assert!(run("(format \"}\")").is_err());
+    }
+
+    #[test]
+    fn test_format_count_mismatch() {
+        assert_eq!(
+            run("(format \"{} {}\" 1)"),
+            Err(
+                "EvaluationError: format template has more placeholders than the 1 values given"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            run("(format \"{}\" 1 2)"),
+            Err(
+                "EvaluationError: format was given 2 values but the template uses fewer"
+                    .to_string()
+            )
+        );
+    }
+}
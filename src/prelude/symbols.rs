@@ -0,0 +1,121 @@
+use super::super::interpretator::{Cast, Context, Scope};
+use super::super::nodes::*;
+use super::define;
+
+pub fn register(scope: &Scope) {
+    define(scope, "gensym", &["&", "prefix"], gensym);
+    define(scope, "symbol", &["name"], symbol);
+    define(scope, "symbol?", &["value"], is_symbol);
+    define(scope, "name", &["value"], name);
+}
+
+/// `(gensym prefix?)` returns a symbol that no other `gensym` call made by
+/// the same interpretator returns, such as `G__1`.
+fn gensym(args: Vec<Value>, _: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let prefix = match &args[..] {
+        [] => "G__".to_string(),
+        [prefix] => prefix.cast_to_string().map_err(|e| e.to_string())?,
+        _ => return Err("gensym expects at most one prefix".to_string()),
+    };
+    ctx.gensym_counter += 1;
+    Ok(Value::Symbol(format!("{}{}", prefix, ctx.gensym_counter)))
+}
+
+/// `(symbol name)` returns the symbol with the given name.
+fn symbol(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    match &args[0] {
+        Value::String(s) if !s.is_empty() => Ok(Value::Symbol(s.clone())),
+        Value::Symbol(s) => Ok(Value::Symbol(s.clone())),
+        v => Err(format!(
+            "symbol expects a non-empty string, got {} {}",
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+fn is_symbol(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Symbol(_))))
+}
+
+/// `(name x)` returns the name of a symbol or keyword as a string.
+fn name(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    match &args[0] {
+        Value::Symbol(s) | Value::Keyword(s) => Ok(Value::String(s.clone())),
+        v => Err(format!(
+            "name expects a symbol or keyword, got {} {}",
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::interpretator::Interpretator;
+    use super::super::run;
+    use super::*;
+
+    #[test]
+    fn test_gensym_unique() {
+        assert_eq!(run("(= (gensym) (gensym))"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(gensym)"), Ok(Value::Symbol("G__1".to_string())));
+        assert_eq!(run("(symbol? (gensym))"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_gensym_prefix() {
+        assert_eq!(
+            run("(gensym) (gensym \"tmp\")"),
+            Ok(Value::Symbol("tmp2".to_string()))
+        );
+        assert_eq!(run("(gensym 'x)"), Ok(Value::Symbol("x1".to_string())));
+        assert!(run("(gensym \"a\" \"b\")").is_err());
+    }
+
+    #[test]
+    fn test_gensym_in_macro() {
+        assert_eq!(
+            run(
+                "(defmacro twice [x] (let [g (gensym)] (list 'do (list 'def g x) (list '+ g g))))
+                 (twice (+ 1 2))"
+            ),
+            Ok(Value::Integer(6))
+        );
+    }
+
+    #[test]
+    fn test_gensym_unique_across_runs() {
+        let mut interpretator = Interpretator::new(None);
+        let first = interpretator.run("(gensym)".to_owned()).unwrap();
+        let second = interpretator.run("(gensym)".to_owned()).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_symbols() {
+        assert_eq!(run("'foo"), Ok(Value::Symbol("foo".to_string())));
+        assert_eq!(run("(= 'foo (symbol \"foo\"))"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(symbol? 'foo)"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(type 'foo)"), Ok(Value::String("symbol".to_string())));
+        assert!(run("(symbol \"\")").is_err());
+    }
+
+    #[test]
+    fn test_symbol_is_not_a_string() {
+        assert_eq!(run("(= 'foo \"foo\")"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(symbol? \"foo\")"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(string? 'foo)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(= (name 'foo) \"foo\")"), Ok(Value::Boolean(true)));
+        assert_eq!(run("(str 'foo)"), Ok(Value::String("foo".to_string())));
+    }
+
+    #[test]
+    fn test_name() {
+        assert_eq!(run("(name :key)"), Ok(Value::String("key".to_string())));
+        assert_eq!(
+            run("(name 1)"),
+            Err("EvaluationError: name expects a symbol or keyword, got integer 1".to_string())
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use super::super::interpretator::{Cast, Context, Scope};
+use super::super::nodes::*;
+use super::define;
+
+pub fn register(scope: &Scope) {
+    define(scope, "number?", &["value"], is_number);
+    define(scope, "integer?", &["value"], is_integer);
+    define(scope, "float?", &["value"], is_float);
+    define(scope, "string?", &["value"], is_string);
+    define(scope, "char?", &["value"], is_char);
+    define(scope, "bool?", &["value"], is_bool);
+    define(scope, "keyword?", &["value"], is_keyword);
+    define(scope, "list?", &["value"], is_list);
+    define(scope, "pair?", &["value"], is_pair);
+    define(scope, "map?", &["value"], is_map);
+    define(scope, "fn?", &["value"], is_fn);
+    define(scope, "nil?", &["value"], is_nil);
+    define(scope, "type", &["value"], type_of);
+    define(scope, "to-number", &["value"], to_number);
+    define(scope, "to-string", &["value"], to_string);
+    define(scope, "to-bool", &["value"], to_bool);
+    define(scope, "number->string", &["n", "radix"], number_to_string);
+    define(
+        scope,
+        "string->number",
+        &["string", "radix"],
+        string_to_number,
+    );
+    define(scope, "deep-clone", &["value"], deep_clone);
+}
+
+fn is_number(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(
+        args[0],
+        Value::Integer(_) | Value::Number(_)
+    )))
+}
+
+fn is_integer(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Integer(_))))
+}
+
+fn is_float(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Number(_))))
+}
+
+fn is_string(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::String(_))))
+}
+
+fn is_char(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Char(_))))
+}
+
+fn is_bool(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Boolean(_))))
+}
+
+fn is_keyword(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Keyword(_))))
+}
+
+fn is_list(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::List(_))))
+}
+
+fn is_pair(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Pair(_, _))))
+}
+
+fn is_map(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Map(_))))
+}
+
+fn is_fn(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Function(_))))
+}
+
+fn is_nil(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::Boolean(matches!(args[0], Value::Null)))
+}
+
+/// `(type value)` returns the name of the value's type, such as "integer"
+/// or "list".
+fn type_of(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(Value::String(args[0].type_name().to_string()))
+}
+
+/// `(to-number value)` parses strings and maps booleans to 0 and 1.
+/// Strings without a fraction or exponent give integers.
+fn to_number(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    match &args[0] {
+        Value::Integer(i) => Ok(Value::Integer(*i)),
+        Value::Boolean(b) => Ok(Value::Integer(*b as i64)),
+        Value::String(s) if s.parse::<i64>().is_ok() => Ok(Value::Integer(s.parse().unwrap())),
+        v => v
+            .cast_to_number()
+            .map(Value::Number)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// The radix argument of the native `name`, between 2 and 36.
+fn radix_arg(name: &str, value: &Value) -> Result<u32, String> {
+    match value {
+        Value::Integer(radix) if (2..=36).contains(radix) => Ok(*radix as u32),
+        v => Err(format!(
+            "{} expects a radix between 2 and 36, got {} {}",
+            name,
+            v.type_name(),
+            v
+        )),
+    }
+}
+
+/// `(number->string n radix)` writes the integer `n` in base `radix`, with
+/// lowercase letters for the digits above 9.
+fn number_to_string(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let n = match &args[0] {
+        Value::Integer(n) => *n,
+        v => {
+            return Err(format!(
+                "number->string expects an integer, got {} {}",
+                v.type_name(),
+                v
+            ))
+        }
+    };
+    let radix = radix_arg("number->string", &args[1])?;
+    let mut magnitude = n.unsigned_abs();
+    let mut digits = vec![];
+    loop {
+        digits.push(std::char::from_digit((magnitude % radix as u64) as u32, radix).unwrap());
+        magnitude /= radix as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    Ok(Value::String(digits.into_iter().rev().collect()))
+}
+
+/// `(string->number string radix)` reads an integer written in base
+/// `radix`, with an optional sign. Letters may be in either case.
+fn string_to_number(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    let s = match &args[0] {
+        Value::String(s) => s,
+        v => {
+            return Err(format!(
+                "string->number expects a string, got {} {}",
+                v.type_name(),
+                v
+            ))
+        }
+    };
+    let radix = radix_arg("string->number", &args[1])?;
+    i64::from_str_radix(s, radix)
+        .map(Value::Integer)
+        .map_err(|_| format!("string->number cannot read {:?} in base {}", s, radix))
+}
+
+/// `(to-string value)` converts numbers, booleans and chars to strings.
+fn to_string(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    args[0]
+        .cast_to_string()
+        .map(Value::String)
+        .map_err(|e| e.to_string())
+}
+
+/// `(to-bool value)` is false for 0 and true for any other number, and
+/// parses the strings "true" and "false".
+fn to_bool(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    args[0]
+        .cast_to_bool()
+        .map(Value::Boolean)
+        .map_err(|e| e.to_string())
+}
+
+/// `(deep-clone value)` returns a copy of `value`. Lists, pairs and maps
+/// are never shared between values, so every builtin already returns
+/// independent copies and this only makes the copy explicit. Functions
+/// keep sharing the scope they were defined in.
+fn deep_clone(args: Vec<Value>, _: &Scope, _: &mut Context) -> Result<Value, String> {
+    Ok(args[0].clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::run;
+    use super::*;
+
+    fn check(predicate: &str, matching: &str, other: &str) {
+        assert_eq!(
+            run(&format!("({} {})", predicate, matching)),
+            Ok(Value::Boolean(true)),
+            "{} {}",
+            predicate,
+            matching
+        );
+        assert_eq!(
+            run(&format!("({} {})", predicate, other)),
+            Ok(Value::Boolean(false)),
+            "{} {}",
+            predicate,
+            other
+        );
+    }
+
+    #[test]
+    fn test_type_predicates() {
+        check("number?", "1.5", "\"1.5\"");
+        check("number?", "1", "\"1\"");
+        check("integer?", "3", "3.0");
+        check("float?", "3.0", "3");
+        check("string?", "\"a\"", "[\"a\"]");
+        check("char?", "\\a", "\"a\"");
+        check("bool?", "false", "0");
+        check("keyword?", ":a", "\"a\"");
+        check("list?", "[1 2]", "{\"a\" 1}");
+        check("pair?", "(cons 1 2)", "[1 2]");
+        check("map?", "{\"a\" 1}", "[1 2]");
+        check("fn?", "(fn [x] x)", "1");
+        check("fn?", "str", "\"str\"");
+        check("nil?", "(get {} \"a\")", "false");
+    }
+
+    #[test]
+    fn test_type() {
+        let cases = [
+            ("1", "integer"),
+            ("1.5", "float"),
+            ("\"a\"", "string"),
+            ("\\a", "char"),
+            ("true", "bool"),
+            (":a", "keyword"),
+            ("[1]", "list"),
+            ("(1 . 2)", "pair"),
+            ("{:a 1}", "map"),
+            ("str", "function"),
+            ("(fn [x] x)", "function"),
+            ("(quote (f x))", "quoted"),
+            ("nil", "null"),
+        ];
+        for (source, name) in cases.iter() {
+            assert_eq!(
+                run(&format!("(type {})", source)),
+                Ok(Value::String(name.to_string())),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_type_in_errors() {
+        assert_eq!(
+            run("(first 5)"),
+            Err("EvaluationError: first expects a list, got integer 5".to_string())
+        );
+        assert_eq!(
+            run("(get [1] 0)"),
+            Err("EvaluationError: get expects a map, got list [1]".to_string())
+        );
+        assert_eq!(
+            run("(+ 1 :a)"),
+            Err("EvaluationError: CastError: Cannot cast keyword to number".to_string())
+        );
+    }
+
+    #[test]
+    fn test_casts() {
+        assert_eq!(run("(to-number \"3.5\")"), Ok(Value::Number(3.5)));
+        assert_eq!(run("(to-number true)"), Ok(Value::Integer(1)));
+        assert_eq!(run("(to-string 42)"), Ok(Value::String("42".to_string())));
+        assert_eq!(run("(to-string \\a)"), Ok(Value::String("a".to_string())));
+        assert_eq!(run("(to-bool 0)"), Ok(Value::Boolean(false)));
+        assert_eq!(run("(to-bool \"true\")"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_cast_errors() {
+        assert_eq!(
+            run("(to-number \"abc\")"),
+            Err("EvaluationError: CastError: failed to cast abc to number".to_string())
+        );
+        assert_eq!(
+            run("(to-bool [1])"),
+            Err("EvaluationError: CastError: Cannot cast list to bool".to_string())
+        );
+    }
+
+    #[test]
+    fn test_deep_clone() {
+        assert_eq!(
+            run("(def a {:k [1 (cons 2 3)]}) (equal? a (deep-clone a))"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            run("(def a [1 [2 3]]) (def b (deep-clone a)) (set! b (append b [4])) a"),
+            run("[1 [2 3]]")
+        );
+    }
+
+    #[test]
+    fn test_number_to_string() {
+        let string = |s: &str| Ok(Value::String(s.to_string()));
+        assert_eq!(run("(number->string 255 16)"), string("ff"));
+        assert_eq!(run("(number->string 5 2)"), string("101"));
+        assert_eq!(run("(number->string 0 2)"), string("0"));
+        assert_eq!(run("(number->string -255 16)"), string("-ff"));
+        assert_eq!(run("(number->string 35 36)"), string("z"));
+        assert_eq!(
+            run("(number->string -9223372036854775808 16)"),
+            string("-8000000000000000")
+        );
+        assert_eq!(
+            run("(number->string 1.5 2)"),
+            Err("EvaluationError: number->string expects an integer, got float 1.5".to_string())
+        );
+        assert_eq!(
+            run("(number->string 1 37)"),
+            Err(
+                "EvaluationError: number->string expects a radix between 2 and 36, got integer 37"
+                    .to_string()
+            )
+        );
+        assert!(run("(number->string 1 1)").is_err());
+    }
+
+    #[test]
+    fn test_string_to_number() {
+        assert_eq!(run("(string->number \"ff\" 16)"), Ok(Value::Integer(255)));
+        assert_eq!(run("(string->number \"FF\" 16)"), Ok(Value::Integer(255)));
+        assert_eq!(run("(string->number \"101\" 2)"), Ok(Value::Integer(5)));
+        assert_eq!(run("(string->number \"-101\" 2)"), Ok(Value::Integer(-5)));
+        assert_eq!(
+            run("(string->number (number->string 123456789 36) 36)"),
+            Ok(Value::Integer(123456789))
+        );
+        assert_eq!(
+            run("(string->number \"12\" 2)"),
+            Err("EvaluationError: string->number cannot read \"12\" in base 2".to_string())
+        );
+        assert_eq!(
+            run("(string->number \"1\" 0)"),
+            Err(
+                "EvaluationError: string->number expects a radix between 2 and 36, got integer 0"
+                    .to_string()
+            )
+        );
+        assert!(run("(string->number 1 10)").is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use super::interpretator::Interpretator;
+use super::lexer::{Lexer, LexerError, Token};
+use super::nodes::Value;
+use std::error;
+use std::io::{self, BufRead, Write};
+
+/// Reads forms line by line, holding back input until every opened paren,
+/// bracket and brace has been closed so a form can span several lines.
+pub struct Repl {
+    pub interpretator: Interpretator,
+    buffer: String,
+}
+
+impl Repl {
+    pub fn new(interpretator: Interpretator) -> Repl {
+        Repl {
+            interpretator,
+            buffer: String::new(),
+        }
+    }
+
+    /// True when part of a form has been read but not evaluated yet.
+    pub fn is_incomplete(&self) -> bool {
+        !self.buffer.trim().is_empty()
+    }
+
+    /// Adds a line of input. Returns the result of evaluating the buffered
+    /// input once it is balanced, or None while more lines are needed.
+    pub fn feed_line(&mut self, line: &str) -> Option<Result<Value, Box<dyn error::Error>>> {
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+        if !self.is_incomplete() {
+            self.buffer.clear();
+            return None;
+        }
+        if unclosed_delimiters(&self.buffer) > 0 {
+            return None;
+        }
+        let source = std::mem::take(&mut self.buffer);
+        Some(self.interpretator.run(source))
+    }
+
+    /// Reads lines from `input` until it ends, writing each result to
+    /// `output`. Input that ends in the middle of a form is reported as an
+    /// error.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            match self.feed_line(&line?) {
+                Some(Ok(v)) => writeln!(output, "{:?}", v)?,
+                Some(Err(e)) => writeln!(output, "{}", e)?,
+                None => {}
+            }
+        }
+        if self.is_incomplete() {
+            self.buffer.clear();
+            writeln!(output, "Unexpected end of input: unclosed form")?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the delimiters opened in `source` but not closed yet. A string
+/// left open also needs more input. Other lexing errors are left for the
+/// interpretator to report, so they count as balanced.
+fn unclosed_delimiters(source: &str) -> usize {
+    let tokens = match Lexer::new(source.to_string()).tokenize() {
+        Ok(tokens) => tokens,
+        Err(LexerError::UnclosedString(_)) => return 1,
+        Err(_) => return 0,
+    };
+    let mut depth: isize = 0;
+    for token in tokens {
+        match token {
+            Token::OpenParen | Token::OpenBracket | Token::OpenBrace => depth += 1,
+            Token::CloseParen | Token::CloseBracket | Token::CloseBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    depth.max(0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repl() -> Repl {
+        Repl::new(Interpretator::new(None))
+    }
+
+    #[test]
+    fn test_repl_single_line() {
+        let mut repl = repl();
+        assert!(matches!(
+            repl.feed_line("(+ 1 2)").unwrap().unwrap(),
+            Value::Integer(3)
+        ));
+        assert!(!repl.is_incomplete());
+    }
+
+    #[test]
+    fn test_repl_multi_line_form() {
+        let mut repl = repl();
+        assert!(repl.feed_line("(defn add [a b]").is_none());
+        assert!(repl.is_incomplete());
+        assert!(repl.feed_line("  (+ a").is_none());
+        assert!(repl.feed_line("     b))").unwrap().is_ok());
+        assert!(!repl.is_incomplete());
+        assert!(repl.feed_line("(add {\"a\" [1").is_none());
+        assert!(repl.feed_line("2]} 3)").unwrap().is_err());
+        assert!(matches!(
+            repl.feed_line("(add 1 2)").unwrap().unwrap(),
+            Value::Integer(3)
+        ));
+    }
+
+    #[test]
+    fn test_repl_multi_line_string() {
+        let mut repl = repl();
+        assert!(repl.feed_line("(str \"a").is_none());
+        assert_eq!(
+            repl.feed_line("b\")").unwrap().unwrap(),
+            Value::String("a\nb".to_string())
+        );
+    }
+
+    #[test]
+    fn test_repl_extra_closer_is_evaluated() {
+        let mut repl = repl();
+        assert!(repl.feed_line("(+ 1 2))").unwrap().is_err());
+        assert!(!repl.is_incomplete());
+    }
+
+    #[test]
+    fn test_repl_blank_lines_are_skipped() {
+        let mut repl = repl();
+        assert!(repl.feed_line("").is_none());
+        assert!(repl.feed_line("   ").is_none());
+        assert!(!repl.is_incomplete());
+    }
+
+    #[test]
+    fn test_repl_run() {
+        let input = "(def a\n  2)\n(* a 3)\n(+ a\n";
+        let mut output = vec![];
+        repl().run(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Integer(2)\nInteger(6)\nUnexpected end of input: unclosed form\n"
+        );
+    }
+}
@@ -0,0 +1,962 @@
+use super::interpretator::{Cast, Context, Scope};
+use super::nodes::*;
+use super::prelude::index_arg;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+type ValueForm = fn(&[Node], &Scope, &mut Context) -> Result<Value, String>;
+type TailForm = for<'n> fn(&'n [Node], &Scope, &mut Context) -> Result<Option<&'n Node>, String>;
+
+/// How a special form produces its value.
+pub enum SpecialForm {
+    /// The form computes its value itself.
+    Value(ValueForm),
+    /// The form's value is the value of the returned sub-form, or null if
+    /// there is none. The caller evaluates it, in tail position.
+    Tail(TailForm),
+}
+
+/// Looks up the special form called `name`. Special forms receive their
+/// arguments unevaluated, so they are checked before the scope lookup.
+pub fn lookup(name: &str) -> Option<SpecialForm> {
+    match name {
+        "defn" => Some(SpecialForm::Value(defn)),
+        "defmacro" => Some(SpecialForm::Value(defmacro)),
+        "fn" => Some(SpecialForm::Value(lambda)),
+        "def" => Some(SpecialForm::Value(def)),
+        "set!" => Some(SpecialForm::Value(set)),
+        "if" => Some(SpecialForm::Tail(if_form)),
+        "cond" => Some(SpecialForm::Tail(cond)),
+        "case" => Some(SpecialForm::Tail(case)),
+        "time" => Some(SpecialForm::Value(time)),
+        "with-output-to-string" => Some(SpecialForm::Value(with_output_to_string)),
+        "quote" => Some(SpecialForm::Value(quote)),
+        "eval" => Some(SpecialForm::Value(eval)),
+        "do" => Some(SpecialForm::Tail(do_form)),
+        "while" => Some(SpecialForm::Value(while_form)),
+        "dotimes" => Some(SpecialForm::Value(dotimes)),
+        "dolist" => Some(SpecialForm::Value(dolist)),
+        "let" => Some(SpecialForm::Value(let_form)),
+        "let*" => Some(SpecialForm::Value(let_star)),
+        "letrec" => Some(SpecialForm::Value(letrec)),
+        "try" => Some(SpecialForm::Value(try_form)),
+        _ => None,
+    }
+}
+
+fn parse_params(node: &Node) -> Result<Vec<String>, String> {
+    let params = match node {
+        Node::Atom(Value::List(params)) => params,
+        _ => return Err(format!("Expected parameter list, got {:?}", node)),
+    };
+    let names = params
+        .iter()
+        .map(|param| match param {
+            Node::Variable(name) => Ok(name.clone()),
+            _ => Err(format!("Parameter must be a name, got {:?}", param)),
+        })
+        .collect::<Result<Vec<String>, String>>()?;
+    match names.iter().position(|name| name == "&") {
+        Some(i) if i + 2 != names.len() || names[i + 1] == "&" => {
+            Err("& must be followed by exactly one rest parameter".to_string())
+        }
+        _ => Ok(names),
+    }
+}
+
+/// `(defn name [params...] body...)`
+fn defn(args: &[Node], scope: &Scope, _: &mut Context) -> Result<Value, String> {
+    if args.len() < 2 {
+        return Err("defn expects a name and a parameter list".to_string());
+    }
+    let name = binding_name("defn", &args[0])?;
+    let function = make_function(&args[1], &args[2..], scope)?;
+    scope.set(name, function.clone());
+    Ok(function)
+}
+
+/// `(defmacro name [params...] body...)` defines a macro. Its parameters
+/// are bound to the quoted argument forms, and the value of its body is
+/// evaluated in place of the call.
+fn defmacro(args: &[Node], scope: &Scope, _: &mut Context) -> Result<Value, String> {
+    if args.len() < 2 {
+        return Err("defmacro expects a name and a parameter list".to_string());
+    }
+    let name = binding_name("defmacro", &args[0])?;
+    let function = match make_function(&args[1], &args[2..], scope)? {
+        Value::Function(Function::UserDefined(f)) => Value::Function(Function::Macro(f)),
+        v => v,
+    };
+    scope.set(name, function.clone());
+    Ok(function)
+}
+
+/// `(fn [params...] body...)`
+fn lambda(args: &[Node], scope: &Scope, _: &mut Context) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("fn expects a parameter list".to_string());
+    }
+    make_function(&args[0], &args[1..], scope)
+}
+
+fn make_function(params: &Node, body: &[Node], scope: &Scope) -> Result<Value, String> {
+    Ok(Value::Function(Function::UserDefined(
+        UserDefinedFunction {
+            args: parse_params(params)?,
+            body: body.into(),
+            scope: scope.clone(),
+        },
+    )))
+}
+
+fn binding_name(form: &str, node: &Node) -> Result<String, String> {
+    match node {
+        Node::Variable(name) => Ok(name.clone()),
+        other => Err(format!("{} expects a name, got {:?}", form, other)),
+    }
+}
+
+/// `(def name value)` binds `value` in the current scope, overwriting any
+/// previous definition.
+fn def(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("def expects a name and a value".to_string());
+    }
+    let name = binding_name("def", &args[0])?;
+    let value = args[1].evaluate(scope, ctx)?;
+    scope.set(name, value.clone());
+    Ok(value)
+}
+
+/// `(set! name value)` rebinds an already defined variable.
+fn set(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    if args.len() != 2 {
+        return Err("set! expects a name and a value".to_string());
+    }
+    let name = binding_name("set!", &args[0])?;
+    let value = args[1].evaluate(scope, ctx)?;
+    if !scope.update(&name, value.clone()) {
+        return Err(format!("{} is not defined", name));
+    }
+    Ok(value)
+}
+
+/// Truthiness used by conditionals: null is false, everything else goes
+/// through the usual bool cast.
+pub(crate) fn is_truthy(value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Null => Ok(false),
+        v => v.cast_to_bool().map_err(|e| e.to_string()),
+    }
+}
+
+/// `(if test then else?)`
+fn if_form<'n>(
+    args: &'n [Node],
+    scope: &Scope,
+    ctx: &mut Context,
+) -> Result<Option<&'n Node>, String> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err("if expects a test, a then branch and an optional else branch".to_string());
+    }
+    if is_truthy(&args[0].evaluate(scope, ctx)?)? {
+        Ok(Some(&args[1]))
+    } else {
+        Ok(args.get(2))
+    }
+}
+
+/// `(cond [test expr]...)` evaluates the expression of the first clause
+/// whose test is truthy, or returns null when none matches.
+fn cond<'n>(
+    args: &'n [Node],
+    scope: &Scope,
+    ctx: &mut Context,
+) -> Result<Option<&'n Node>, String> {
+    for clause in args {
+        let (test, expr) = match clause {
+            Node::Atom(Value::List(nodes)) if nodes.len() == 2 => (&nodes[0], &nodes[1]),
+            _ => return Err(format!("cond clause must be [test expr], got {:?}", clause)),
+        };
+        if is_truthy(&test.evaluate(scope, ctx)?)? {
+            return Ok(Some(expr));
+        }
+    }
+    Ok(None)
+}
+
+/// `(case value [key expr]...)` evaluates the expression of the first
+/// clause whose key is `equal?` to `value`. Keys are literals and are not
+/// evaluated, and `_` matches anything. Returns null when nothing matches.
+fn case<'n>(
+    args: &'n [Node],
+    scope: &Scope,
+    ctx: &mut Context,
+) -> Result<Option<&'n Node>, String> {
+    let (value, clauses) = match args.split_first() {
+        Some((value, clauses)) => (value.evaluate(scope, ctx)?, clauses),
+        None => return Err("case expects a value".to_string()),
+    };
+    for clause in clauses {
+        let (key, expr) = match clause {
+            Node::Atom(Value::List(nodes)) if nodes.len() == 2 => (&nodes[0], &nodes[1]),
+            _ => return Err(format!("case clause must be [key expr], got {}", clause)),
+        };
+        let matches = match key {
+            Node::Variable(name) if name == "_" => true,
+            Node::Atom(key) => *key == value,
+            key => return Err(format!("case key must be a literal or _, got {}", key)),
+        };
+        if matches {
+            return Ok(Some(expr));
+        }
+    }
+    Ok(None)
+}
+
+/// `(quote form)` returns `form` unevaluated. A name gives a symbol, and
+/// literals made only of names and other literals, such as `[a 1]` or
+/// `(1 . 2)`, give their value with names as symbols. Any other form is
+/// kept as a quoted form.
+fn quote(args: &[Node], _: &Scope, _: &mut Context) -> Result<Value, String> {
+    match args {
+        [form] => Ok(datum(form).unwrap_or_else(|| Value::Quoted(Box::new(form.clone())))),
+        _ => Err("quote expects exactly one form".to_string()),
+    }
+}
+
+/// The value a quoted form stands for, if it can be written as one.
+fn datum(node: &Node) -> Option<Value> {
+    let atom = |node: &Node| datum(node).map(Node::Atom);
+    match node {
+        Node::Variable(name) => Some(Value::Symbol(name.clone())),
+        Node::Atom(Value::List(nodes)) => {
+            Some(Value::List(nodes.iter().map(atom).collect::<Option<_>>()?))
+        }
+        Node::Atom(Value::Pair(head, tail)) => {
+            Some(Value::Pair(Box::new(atom(head)?), Box::new(atom(tail)?)))
+        }
+        Node::Atom(v) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+/// `(time expr)` evaluates `expr`, writes how long that took to the output
+/// and returns its value.
+fn time(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("time expects exactly one expression".to_string());
+    }
+    let start = ctx.clock.now();
+    let value = args[0].evaluate(scope, ctx)?;
+    let elapsed = (ctx.clock.now() - start) * 1000.0;
+    writeln!(ctx.output, "Elapsed time: {} ms", elapsed)
+        .and_then(|_| ctx.output.flush())
+        .map_err(|e| format!("failed to write output: {}", e))?;
+    Ok(value)
+}
+
+/// An output sink that appends to a buffer shared with its creator.
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `(with-output-to-string body...)` evaluates the body with the output
+/// sink redirected into a buffer, and returns what was written as a
+/// string. The previous sink is restored afterwards, even if the body
+/// fails.
+fn with_output_to_string(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let buffer = Rc::new(RefCell::new(Vec::new()));
+    let previous = std::mem::replace(&mut ctx.output, Box::new(Capture(buffer.clone())));
+    let result = evaluate_body(args, scope, ctx);
+    ctx.output = previous;
+    result?;
+    let bytes = buffer.borrow().clone();
+    String::from_utf8(bytes)
+        .map(Value::String)
+        .map_err(|_| "with-output-to-string captured invalid UTF-8".to_string())
+}
+
+/// `(eval value)` evaluates a quoted form in the current scope, and looks
+/// up the variable a symbol names. Any other value is already evaluated
+/// and is returned as is.
+fn eval(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    if args.len() != 1 {
+        return Err("eval expects exactly one argument".to_string());
+    }
+    match args[0].evaluate(scope, ctx)? {
+        Value::Quoted(form) => form.evaluate(scope, ctx),
+        Value::Symbol(name) => Node::Variable(name).evaluate(scope, ctx),
+        v => Ok(v),
+    }
+}
+
+/// `(do forms...)` evaluates the forms in order and returns the last value.
+fn do_form<'n>(
+    args: &'n [Node],
+    scope: &Scope,
+    ctx: &mut Context,
+) -> Result<Option<&'n Node>, String> {
+    match args.split_last() {
+        Some((last, init)) => {
+            for node in init {
+                node.evaluate(scope, ctx)?;
+            }
+            Ok(Some(last))
+        }
+        None => Ok(None),
+    }
+}
+
+/// `(while test body...)` evaluates the body for as long as `test` is
+/// truthy and returns the last body value, or null if it never ran.
+/// Fails once the body has run `max_iterations` times, if that is set.
+fn while_form(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let (test, body) = match args.split_first() {
+        Some(split) => split,
+        None => return Err("while expects a test".to_string()),
+    };
+    let mut result = Value::Null;
+    let mut iterations = 0;
+    while is_truthy(&test.evaluate(scope, ctx)?)? {
+        ctx.check_iterations(iterations)?;
+        result = evaluate_body(body, scope, ctx)?;
+        iterations += 1;
+    }
+    Ok(result)
+}
+
+/// Splits the `[name value]` head of a loop form from its body.
+fn loop_binding<'n>(
+    form: &str,
+    args: &'n [Node],
+) -> Result<(String, &'n Node, &'n [Node]), String> {
+    let (binding, body) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(format!("{} expects a [name value] binding", form)),
+    };
+    match parse_bindings(form, binding)?.as_slice() {
+        [(name, value)] => Ok((name.clone(), value, body)),
+        _ => Err(format!("{} expects a [name value] binding", form)),
+    }
+}
+
+/// `(dotimes [i n] body...)` evaluates the body `n` times with `i` bound to
+/// 0 through `n - 1` in a fresh scope each time. `n` must be a
+/// non-negative integer. Returns null.
+fn dotimes(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let (name, count, body) = loop_binding("dotimes", args)?;
+    let count = index_arg("dotimes", &count.evaluate(scope, ctx)?)?;
+    for i in 0..count {
+        let iteration_scope = Scope::new(Some(scope));
+        iteration_scope.set(name.clone(), Value::Integer(i as i64));
+        evaluate_body(body, &iteration_scope, ctx)?;
+    }
+    Ok(Value::Null)
+}
+
+/// `(dolist [x list] body...)` evaluates the body once per element of
+/// `list`, with `x` bound to it in a fresh scope each time. Returns null.
+fn dolist(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let (name, list, body) = loop_binding("dolist", args)?;
+    let elements = match list.evaluate(scope, ctx)? {
+        Value::List(elements) => elements,
+        v => return Err(format!("dolist expects a list, got {}", v)),
+    };
+    for element in elements {
+        let iteration_scope = Scope::new(Some(scope));
+        iteration_scope.set(name.clone(), element.evaluate(scope, ctx)?);
+        evaluate_body(body, &iteration_scope, ctx)?;
+    }
+    Ok(Value::Null)
+}
+
+/// Splits `[name value...]` into its name and value pairs.
+fn parse_bindings<'n>(form: &str, node: &'n Node) -> Result<Vec<(String, &'n Node)>, String> {
+    let nodes = match node {
+        Node::Atom(Value::List(nodes)) if nodes.len() % 2 == 0 => nodes,
+        _ => return Err(format!("{} expects a [name value...] binding list", form)),
+    };
+    nodes
+        .chunks(2)
+        .map(|pair| Ok((binding_name(form, &pair[0])?, &pair[1])))
+        .collect()
+}
+
+fn evaluate_body(body: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let mut result = Value::Null;
+    for node in body {
+        result = node.evaluate(scope, ctx)?;
+    }
+    Ok(result)
+}
+
+/// `(let [name value...] body...)` evaluates every value in the enclosing
+/// scope, so a value cannot refer to the other names, then binds them all
+/// in a new scope for the body.
+fn let_form(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let (bindings, body) = match args.split_first() {
+        Some((bindings, body)) => (parse_bindings("let", bindings)?, body),
+        None => return Err("let expects a binding list".to_string()),
+    };
+    let mut values = vec![];
+    for (name, node) in bindings {
+        values.push((name, node.evaluate(scope, ctx)?));
+    }
+    let let_scope = Scope::new(Some(scope));
+    for (name, value) in values {
+        let_scope.set(name, value);
+    }
+    evaluate_body(body, &let_scope, ctx)
+}
+
+/// `(let* [name value...] body...)` binds the names one after another, so
+/// each value can refer to the names bound before it.
+fn let_star(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let (bindings, body) = match args.split_first() {
+        Some((bindings, body)) => (parse_bindings("let*", bindings)?, body),
+        None => return Err("let* expects a binding list".to_string()),
+    };
+    let let_scope = Scope::new(Some(scope));
+    for (name, node) in bindings {
+        let value = node.evaluate(&let_scope, ctx)?;
+        let_scope.set(name, value);
+    }
+    evaluate_body(body, &let_scope, ctx)
+}
+
+/// `(letrec [name value...] body...)` binds every name to null before
+/// evaluating any value, so that the values, typically functions, can
+/// refer to each other and to names bound after them.
+fn letrec(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let (bindings, body) = match args.split_first() {
+        Some((bindings, body)) => (parse_bindings("letrec", bindings)?, body),
+        None => return Err("letrec expects a binding list".to_string()),
+    };
+    let let_scope = Scope::new(Some(scope));
+    for (name, _) in &bindings {
+        let_scope.set(name.clone(), Value::Null);
+    }
+    for (name, node) in bindings {
+        let value = node.evaluate(&let_scope, ctx)?;
+        let_scope.set(name, value);
+    }
+    evaluate_body(body, &let_scope, ctx)
+}
+
+/// `(try body... (catch name handler...))` evaluates the body and, if it
+/// fails, evaluates the handler with the error message bound to `name`.
+fn try_form(args: &[Node], scope: &Scope, ctx: &mut Context) -> Result<Value, String> {
+    let (body, name, handler) = match args.split_last() {
+        Some((Node::FunctionCall(catch, clause), body)) if catch == "catch" => {
+            match clause.split_first() {
+                Some((name, handler)) => (body, binding_name("catch", name)?, handler),
+                None => return Err("catch expects a name".to_string()),
+            }
+        }
+        _ => return Err("try expects a (catch name handler...) clause last".to_string()),
+    };
+    match evaluate_body(body, scope, ctx) {
+        Ok(v) => Ok(v),
+        Err(message) => {
+            let catch_scope = Scope::new(Some(scope));
+            catch_scope.set(name, Value::String(message));
+            evaluate_body(handler, &catch_scope, ctx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::interpretator::Interpretator;
+    use super::*;
+
+    fn run(source: &str) -> Result<Value, String> {
+        Interpretator::new(None)
+            .run(source.to_owned())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_def() {
+        assert_eq!(run("(def e 2.5)"), Ok(Value::Number(2.5)));
+        assert_eq!(run("(def e 2.5) e"), Ok(Value::Number(2.5)));
+    }
+
+    #[test]
+    fn test_def_redefine() {
+        assert_eq!(
+            run("(def a 1) (def a \"two\") a"),
+            Ok(Value::String("two".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        assert_eq!(run("(def a 1) (set! a 2) a"), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_set_undefined() {
+        assert_eq!(
+            run("(set! a 2)"),
+            Err("EvaluationError: a is not defined".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fn() {
+        assert_eq!(
+            run("(def second (fn [a b] b)) (second 1 2)"),
+            Ok(Value::Integer(2))
+        );
+        assert!(run("(fn)").is_err());
+        assert!(run("(fn [1] 1)").is_err());
+    }
+
+    #[test]
+    fn test_fn_closure() {
+        assert_eq!(
+            run("(defn make-adder [n] (fn [x] (+ x n)))
+                 (def add5 (make-adder 5))
+                 (def add10 (make-adder 10))
+                 [(add5 1) (add10 1)]"),
+            run("[6 11]")
+        );
+    }
+
+    #[test]
+    fn test_fn_closure_shares_captured_bindings() {
+        assert_eq!(
+            run("(defn make-counter [] (let [n 0] (fn [] (set! n (+ n 1)))))
+                 (def counter (make-counter))
+                 (counter) (counter) (counter)"),
+            Ok(Value::Integer(3))
+        );
+    }
+
+    #[test]
+    fn test_fn_body_does_not_see_caller_scope() {
+        assert_eq!(
+            run("(defn read-local [] local)
+                 (defn caller [local] (read-local))
+                 (caller 1)"),
+            Err("EvaluationError: local is not defined".to_string())
+        );
+    }
+
+    #[test]
+    fn test_if() {
+        assert_eq!(run("(if true 1 2)"), Ok(Value::Integer(1)));
+        assert_eq!(run("(if false 1 2)"), Ok(Value::Integer(2)));
+        assert_eq!(run("(if false 1)"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_if_evaluates_only_taken_branch() {
+        assert_eq!(run("(if true 1 (undefined))"), Ok(Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_dotimes() {
+        assert_eq!(
+            run("(def total 0) (dotimes [i 5] (set! total (+ total i))) total"),
+            Ok(Value::Integer(10))
+        );
+        assert_eq!(run("(dotimes [i 3] i)"), Ok(Value::Null));
+        assert_eq!(
+            run("(def n 0) (dotimes [i 0] (set! n 1)) n"),
+            Ok(Value::Integer(0))
+        );
+        assert_eq!(
+            run("(def n 0) (dotimes [i 2.0] (set! n (+ n 1))) n"),
+            Ok(Value::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_dotimes_invalid_count() {
+        assert_eq!(
+            run("(dotimes [i 2.5] i)"),
+            Err(
+                "EvaluationError: dotimes expects a non-negative integer index, got 2.5"
+                    .to_string()
+            )
+        );
+        assert!(run("(dotimes [i -1] i)").is_err());
+        assert!(run("(dotimes [i \"3\"] i)").is_err());
+        assert!(run("(dotimes [i nil] i)").is_err());
+    }
+
+    #[test]
+    fn test_dolist() {
+        assert_eq!(
+            run("(def s \"\") (dolist [x [\"a\" \"b\" \"c\"]] (set! s (str x s))) s"),
+            Ok(Value::String("cba".to_string()))
+        );
+        assert_eq!(
+            run("(def total 0) (dolist [x (range 4)] (set! total (+ total x)) (set! total (+ total 1))) total"),
+            Ok(Value::Integer(10))
+        );
+    }
+
+    #[test]
+    fn test_loop_variable_scope() {
+        assert!(run("(dotimes [i 2] i) i").is_err());
+        assert_eq!(
+            run("(def fs []) (dolist [x [1 2]] (set! fs (append fs [(fn [] x)]))) (def f (first fs)) (f)"),
+            Ok(Value::Integer(1))
+        );
+    }
+
+    #[test]
+    fn test_loop_invalid_binding() {
+        assert!(run("(dotimes [i] 1)").is_err());
+        assert!(run("(dotimes [i 1 j 2] 1)").is_err());
+        assert!(run("(dolist [x 1] x)").is_err());
+        assert!(run("(dolist)").is_err());
+    }
+
+    #[test]
+    fn test_case() {
+        let source =
+            "(defn name [x] (case x [1 \"one\"] [2 \"two\"] [\"2\" \"string\"] [_ \"other\"]))";
+        assert_eq!(
+            run(&format!("{} (name 2)", source)),
+            Ok(Value::String("two".to_string()))
+        );
+        assert_eq!(
+            run(&format!("{} (name \"2\")", source)),
+            Ok(Value::String("string".to_string()))
+        );
+        assert_eq!(
+            run("(case [1 :a] [[1 :b] 1] [[1 :a] 2])"),
+            Ok(Value::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_case_wildcard() {
+        assert_eq!(
+            run("(case 5 [1 \"one\"] [_ \"other\"] [5 \"five\"])"),
+            Ok(Value::String("other".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_case_no_match() {
+        assert_eq!(run("(case 3 [1 \"one\"] [2 (undefined)])"), Ok(Value::Null));
+        assert_eq!(run("(case 3)"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_case_keys_are_not_evaluated() {
+        assert!(run("(def one 1) (case 1 [one \"one\"])").is_err());
+        assert_eq!(
+            run("(case 2 [(+ 1 1) \"two\"])"),
+            Err("EvaluationError: case key must be a literal or _, got (+ 1 1)".to_string())
+        );
+        assert!(run("(case 1 [1])").is_err());
+        assert!(run("(case)").is_err());
+    }
+
+    #[test]
+    fn test_cond_first_match() {
+        assert_eq!(
+            run("(cond [false 1] [true 2] [true (undefined)])"),
+            Ok(Value::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_cond_default() {
+        assert_eq!(
+            run("(def a 0) (cond [a \"a\"] [false \"b\"] [true \"default\"])"),
+            Ok(Value::String("default".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_cond_no_match() {
+        assert_eq!(run("(cond [false 1] [0 2])"), Ok(Value::Null));
+        assert_eq!(run("(cond)"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_cond_invalid_clause() {
+        assert!(run("(cond [true])").is_err());
+        assert!(run("(cond [true 1 2])").is_err());
+        assert!(run("(cond 1)").is_err());
+    }
+
+    #[test]
+    fn test_quote_literals() {
+        assert_eq!(run("'5"), Ok(Value::Integer(5)));
+        assert_eq!(run("(first '[1 2])"), Ok(Value::Integer(1)));
+        assert_eq!(
+            run("(equal? '(1 . [2]) (cons 1 [2]))"),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(run("'[1 x]"), run("[1 (symbol \"x\")]"));
+        assert!(matches!(run("'[1 (f x)]"), Ok(Value::Quoted(_))));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(
+            run("(quote (str 1 2))"),
+            Ok(Value::Quoted(Box::new(Node::FunctionCall(
+                "str".to_string(),
+                vec![Node::Atom(Value::Integer(1)), Node::Atom(Value::Integer(2))]
+            ))))
+        );
+        assert_eq!(
+            run("(quote undefined)"),
+            Ok(Value::Symbol("undefined".to_string()))
+        );
+        assert!(run("(quote 1 2)").is_err());
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(
+            run("(eval (quote (str 1 2)))"),
+            Ok(Value::String("12".to_string()))
+        );
+        assert_eq!(
+            run("(def form (quote x)) (def x 5) (eval form)"),
+            Ok(Value::Integer(5))
+        );
+        assert_eq!(run("(eval 3)"), Ok(Value::Integer(3)));
+    }
+
+    #[test]
+    fn test_do() {
+        assert_eq!(
+            run("(do (def a 1) (def b 2) (+ a b))"),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(run("(do)"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_do_runs_in_order() {
+        assert_eq!(
+            run("(def a 1) (do (set! a (+ a 1)) (set! a (* a 10))) a"),
+            Ok(Value::Integer(20))
+        );
+    }
+
+    #[test]
+    fn test_do_in_if_branch() {
+        assert_eq!(
+            run("(def a 0) (if true (do (set! a 1) (+ a 1)) 0)"),
+            Ok(Value::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_while() {
+        assert_eq!(
+            run("(def i 0) (while (< i 5) (set! i (+ i 1))) i"),
+            Ok(Value::Integer(5))
+        );
+        assert_eq!(
+            run("(def i 0) (while (< i 5) (set! i (+ i 1)) (* i 10))"),
+            Ok(Value::Integer(50))
+        );
+    }
+
+    #[test]
+    fn test_while_never_runs() {
+        assert_eq!(run("(while false (undefined))"), Ok(Value::Null));
+        assert!(run("(while)").is_err());
+    }
+
+    #[test]
+    fn test_rest_params() {
+        let sum = "(defn sum [& nums] (apply + nums)) ";
+        assert_eq!(run(&format!("{}(sum)", sum)), Ok(Value::Integer(0)));
+        assert_eq!(run(&format!("{}(sum 4)", sum)), Ok(Value::Integer(4)));
+        assert_eq!(run(&format!("{}(sum 1 2 3)", sum)), Ok(Value::Integer(6)));
+    }
+
+    #[test]
+    fn test_rest_params_after_fixed_params() {
+        let tail = "(defn tail [a b & more] [a b more]) ";
+        assert_eq!(run(&format!("{}(tail 1 2)", tail)), run("[1 2 []]"));
+        assert_eq!(run(&format!("{}(tail 1 2 3 4)", tail)), run("[1 2 [3 4]]"));
+        assert_eq!(
+            run(&format!("{}(tail 1)", tail)),
+            Err(
+                "EvaluationError: Function tail takes at least 2 arguments, but 1 were given"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_rest_params_invalid() {
+        assert!(run("(fn [&] 1)").is_err());
+        assert!(run("(fn [& a b] 1)").is_err());
+        assert!(run("(fn [& &] 1)").is_err());
+        assert!(run("(fn [a & b & c] 1)").is_err());
+    }
+
+    #[test]
+    fn test_let() {
+        assert_eq!(run("(let [a 1 b 2] (+ a b))"), Ok(Value::Integer(3)));
+        assert_eq!(run("(let [] 1)"), Ok(Value::Integer(1)));
+        assert_eq!(run("(let [a 1])"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_let_values_see_enclosing_scope() {
+        assert_eq!(run("(def a 10) (let [a 1 b a] b)"), Ok(Value::Integer(10)));
+    }
+
+    #[test]
+    fn test_let_does_not_leak() {
+        assert_eq!(run("(def a 1) (let [a 2] a) a"), Ok(Value::Integer(1)));
+        assert!(run("(let [b 2] b) b").is_err());
+    }
+
+    #[test]
+    fn test_let_star_is_sequential() {
+        assert_eq!(run("(let* [a 1 b (+ a 1)] b)"), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_letrec_mutual_recursion() {
+        assert_eq!(
+            run("(letrec [even? (fn [n] (if (= n 0) true (odd? (+ n -1))))
+                          odd? (fn [n] (if (= n 0) false (even? (+ n -1))))]
+                   [(even? 10) (odd? 7) (even? 3)])"),
+            run("[true true false]")
+        );
+    }
+
+    #[test]
+    fn test_letrec_names_start_as_null() {
+        assert_eq!(run("(letrec [a b b 1] a)"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_let_invalid_bindings() {
+        assert!(run("(let)").is_err());
+        assert!(run("(let [a] a)").is_err());
+        assert!(run("(let* [1 2] 1)").is_err());
+        assert!(run("(letrec 1 1)").is_err());
+    }
+
+    #[test]
+    fn test_try_without_error() {
+        assert_eq!(
+            run("(try (def a 1) (+ a 1) (catch e 0))"),
+            Ok(Value::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_try_catches_error() {
+        assert_eq!(
+            run("(try (undefined) (catch e (str \"caught: \" e)))"),
+            Ok(Value::String(
+                "caught: undefined is not defined".to_string()
+            ))
+        );
+        assert_eq!(run("(try (/ 1 0) (catch e))"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_try_error_in_handler_propagates() {
+        assert_eq!(
+            run("(try (undefined) (catch e (other)))"),
+            Err("EvaluationError: other is not defined".to_string())
+        );
+    }
+
+    #[test]
+    fn test_try_invalid() {
+        assert!(run("(try 1)").is_err());
+        assert!(run("(try 1 (catch))").is_err());
+        assert!(run("(try 1 (catch 2 3))").is_err());
+    }
+
+    #[test]
+    fn test_defmacro() {
+        let unless = "(defmacro unless [c body] (list 'if c nil body)) ";
+        assert_eq!(
+            run(&format!("{}(unless false 1)", unless)),
+            Ok(Value::Integer(1))
+        );
+        assert_eq!(
+            run(&format!("{}(unless true (undefined))", unless)),
+            Ok(Value::Null)
+        );
+    }
+
+    #[test]
+    fn test_defmacro_expands_in_caller_scope() {
+        assert_eq!(
+            run(
+                "(defmacro with-x [value body] (list 'do (list 'def 'x value) body))
+                 (defn show [y] (with-x 1 [x y]))
+                 (show 2)"
+            ),
+            run("[1 2]")
+        );
+    }
+
+    #[test]
+    fn test_defmacro_rest_params() {
+        assert_eq!(
+            run("(defmacro my-do [& forms] (apply list 'do forms))
+                 (def a 1)
+                 (my-do (set! a (+ a 1)) (* a 10))"),
+            Ok(Value::Integer(20))
+        );
+    }
+
+    #[test]
+    fn test_defmacro_computes_on_arguments() {
+        assert_eq!(
+            run("(defmacro add1 [x] (+ x 1)) (add1 5)"),
+            Ok(Value::Integer(6))
+        );
+        assert_eq!(
+            run("(defmacro size [xs] (length xs)) (size [a (b) 3])"),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(
+            run("(defmacro head [xs] (apply (fn [x & _] x) xs)) (def a 7) (head [a 2])"),
+            Ok(Value::Integer(7))
+        );
+    }
+
+    #[test]
+    fn test_defmacro_expansion_depth_limited() {
+        assert_eq!(
+            run("(defmacro forever [] '(forever)) (forever)"),
+            Err("EvaluationError: recursion limit exceeded".to_string())
+        );
+        assert_eq!(
+            run("(defmacro forever [] '(do 1 (forever))) (forever)"),
+            Err("EvaluationError: recursion limit exceeded".to_string())
+        );
+    }
+
+    #[test]
+    fn test_macro_not_callable_as_function() {
+        assert!(run("(defmacro m [x] x) (apply m [1])").is_err());
+    }
+}